@@ -0,0 +1,330 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+use chrono::{DateTime, SecondsFormat, SubsecRound, TimeDelta, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::ArchiveConfig,
+    source::{Event, FetchOptions, Revision},
+};
+
+/// A record of every event each source has listed, so that events stay
+/// available after upstream stops listing them.
+///
+/// Events are kept per source and per set of fetch options, since a source
+/// lists different events depending on the options. The revisions of each
+/// event are tracked per source, by a hash of its contents, since sources
+/// that list the same event can describe it differently.
+#[derive(Debug, Clone)]
+pub(crate) struct Archive {
+    connection: Arc<Mutex<Connection>>,
+    /// How long upcoming events that upstream stops listing are kept, marked
+    /// as cancelled.
+    cancelled_grace: TimeDelta,
+}
+
+impl Archive {
+    /// Open the SQLite database at `path`, creating it if it doesn't exist.
+    pub(crate) fn open(config: &ArchiveConfig) -> Result<Self, anyhow::Error> {
+        let path = &config.path;
+        let connection =
+            Connection::open(path).with_context(|| format!("failed to open archive {path}"))?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS events (
+                source TEXT NOT NULL,
+                options TEXT NOT NULL,
+                origin TEXT NOT NULL,
+                uid TEXT NOT NULL,
+                starts_at TEXT NOT NULL,
+                first_seen TEXT NOT NULL,
+                last_seen TEXT NOT NULL,
+                event TEXT NOT NULL,
+                PRIMARY KEY (source, options, origin, uid)
+            );
+            CREATE INDEX IF NOT EXISTS events_by_start ON events (source, options, starts_at);
+            CREATE TABLE IF NOT EXISTS revisions (
+                source TEXT NOT NULL,
+                origin TEXT NOT NULL,
+                uid TEXT NOT NULL,
+                hash TEXT NOT NULL,
+                sequence INTEGER NOT NULL,
+                created INTEGER NOT NULL,
+                modified INTEGER NOT NULL,
+                PRIMARY KEY (source, origin, uid)
+            );",
+        )?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            cancelled_grace: TimeDelta::days(config.cancelled_grace_days.into()),
+        })
+    }
+
+    /// Revise and record the events a source has just listed, then add the
+    /// upcoming events it listed recently but no longer does, as cancelled.
+    ///
+    /// A source that lists no events at all is more likely to be broken than
+    /// to have had every event cancelled, so nothing is cancelled then.
+    pub(crate) async fn update(
+        &self,
+        source: &str,
+        options: &FetchOptions,
+        events: &mut Vec<Event>,
+    ) -> Result<(), anyhow::Error> {
+        if events.is_empty() {
+            tracing::warn!(source, "upstream listed no events, so none are cancelled");
+            return Ok(());
+        }
+        let now = Utc::now();
+        let listed = events
+            .iter()
+            .map(|event| (event.origin.clone(), event.uid.clone()))
+            .collect::<HashSet<_>>();
+        let vanished = self
+            .events_seen_since(source, options, now, now - self.cancelled_grace)
+            .await?
+            .into_iter()
+            .filter(|event| !listed.contains(&(event.origin.clone(), event.uid.clone())))
+            .map(|mut event| {
+                if !event.cancelled {
+                    event.cancelled = true;
+                    event
+                        .add_note("This event is no longer listed, so it may have been cancelled.");
+                }
+                event
+            });
+        let count = events.len();
+        events.extend(vanished);
+
+        self.revise(source, events).await?;
+        self.record(source, options, &events[..count]).await
+    }
+
+    /// Record the events a source listed, replacing earlier copies of them.
+    pub(crate) async fn record(
+        &self,
+        source: &str,
+        options: &FetchOptions,
+        events: &[Event],
+    ) -> Result<(), anyhow::Error> {
+        let now = timestamp(Utc::now());
+        let rows = events
+            .iter()
+            .filter_map(|event| {
+                let starts_at = timestamp(event.starts_at()?);
+                Some((event.origin.clone(), event.uid.clone(), starts_at, event))
+            })
+            .map(|(origin, uid, starts_at, event)| {
+                Ok((origin, uid, starts_at, serde_json::to_string(event)?))
+            })
+            .collect::<Result<Vec<_>, serde_json::Error>>()?;
+        let source = source.to_owned();
+        let options = serde_json::to_string(options)?;
+
+        self.run(move |connection| {
+            let transaction = connection.transaction()?;
+            {
+                let mut insert = transaction.prepare(
+                    "INSERT INTO events
+                        (source, options, origin, uid, starts_at, first_seen, last_seen, event)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6, ?7)
+                    ON CONFLICT (source, options, origin, uid) DO UPDATE SET
+                        starts_at = excluded.starts_at,
+                        last_seen = excluded.last_seen,
+                        event = excluded.event",
+                )?;
+                for (origin, uid, starts_at, event) in &rows {
+                    insert.execute(params![source, options, origin, uid, starts_at, now, event])?;
+                }
+            }
+            transaction.commit()
+        })
+        .await
+    }
+
+    /// Set the revision of each event a source listed, counting a new revision
+    /// for each event whose contents have changed since the source last listed
+    /// it.
+    ///
+    /// A changed event is modified when upstream says it was updated, if
+    /// that's known and later than the previous revision, or else now.
+    pub(crate) async fn revise(
+        &self,
+        source: &str,
+        events: &mut [Event],
+    ) -> Result<(), anyhow::Error> {
+        let now = Utc::now().trunc_subsecs(0);
+        let source = source.to_owned();
+        let rows = events
+            .iter()
+            .map(|event| {
+                let hash = content_hash(event)?;
+                Ok((event.origin.clone(), event.uid.clone(), hash, event.updated))
+            })
+            .collect::<Result<Vec<_>, serde_json::Error>>()?;
+
+        let revisions = self
+            .run(move |connection| {
+                let transaction = connection.transaction()?;
+                let mut revisions = Vec::new();
+                {
+                    let mut select = transaction.prepare(
+                        "SELECT hash, sequence, created, modified FROM revisions
+                        WHERE source = ?1 AND origin = ?2 AND uid = ?3",
+                    )?;
+                    let mut upsert = transaction.prepare(
+                        "INSERT OR REPLACE INTO revisions
+                            (source, origin, uid, hash, sequence, created, modified)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    )?;
+                    for (origin, uid, hash, updated) in &rows {
+                        let previous = select
+                            .query_row(params![source, origin, uid], |row| {
+                                Ok((
+                                    row.get::<_, String>(0)?,
+                                    Revision {
+                                        sequence: row.get(1)?,
+                                        created: from_seconds(row.get(2)?),
+                                        modified: from_seconds(row.get(3)?),
+                                    },
+                                ))
+                            })
+                            .optional()?;
+                        let revision = match previous {
+                            Some((previous_hash, revision)) if previous_hash == *hash => {
+                                revisions.push(revision);
+                                continue;
+                            }
+                            Some((_, previous)) => Revision {
+                                sequence: previous.sequence + 1,
+                                created: previous.created,
+                                modified: updated
+                                    .filter(|updated| *updated > previous.modified)
+                                    .unwrap_or(now),
+                            },
+                            None => Revision {
+                                sequence: 0,
+                                created: now,
+                                modified: updated.unwrap_or(now),
+                            },
+                        };
+                        upsert.execute(params![
+                            source,
+                            origin,
+                            uid,
+                            hash,
+                            revision.sequence,
+                            revision.created.timestamp(),
+                            revision.modified.timestamp(),
+                        ])?;
+                        revisions.push(revision);
+                    }
+                }
+                transaction.commit()?;
+                Ok(revisions)
+            })
+            .await?;
+
+        for (event, revision) in events.iter_mut().zip(revisions) {
+            event.revision = Some(revision);
+        }
+        Ok(())
+    }
+
+    /// The events a source has listed since `seen`, that start after `from`.
+    async fn events_seen_since(
+        &self,
+        source: &str,
+        options: &FetchOptions,
+        from: DateTime<Utc>,
+        seen: DateTime<Utc>,
+    ) -> Result<Vec<Event>, anyhow::Error> {
+        let source = source.to_owned();
+        let options = serde_json::to_string(options)?;
+        let rows = self
+            .run(move |connection| {
+                connection
+                    .prepare(
+                        "SELECT event FROM events
+                        WHERE source = ?1 AND options = ?2 AND starts_at >= ?3 AND last_seen >= ?4
+                        ORDER BY starts_at",
+                    )?
+                    .query_map(
+                        params![source, options, timestamp(from), timestamp(seen)],
+                        |row| row.get::<_, String>(0),
+                    )?
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .await?;
+        rows.iter()
+            .map(|row| Ok(serde_json::from_str(row)?))
+            .collect()
+    }
+
+    /// The events a source has listed that start in `[from, until)`, in order
+    /// of when they start.
+    pub(crate) async fn events(
+        &self,
+        source: &str,
+        options: &FetchOptions,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<Event>, anyhow::Error> {
+        let source = source.to_owned();
+        let options = serde_json::to_string(options)?;
+        let rows = self
+            .run(move |connection| {
+                connection
+                    .prepare(
+                        "SELECT event FROM events
+                        WHERE source = ?1 AND options = ?2 AND starts_at >= ?3 AND starts_at < ?4
+                        ORDER BY starts_at",
+                    )?
+                    .query_map(
+                        params![source, options, timestamp(from), timestamp(until)],
+                        |row| row.get::<_, String>(0),
+                    )?
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .await?;
+        rows.iter()
+            .map(|row| Ok(serde_json::from_str(row)?))
+            .collect()
+    }
+
+    /// Run a query on a blocking thread.
+    async fn run<T: Send + 'static>(
+        &self,
+        query: impl FnOnce(&mut Connection) -> Result<T, rusqlite::Error> + Send + 'static,
+    ) -> Result<T, anyhow::Error> {
+        let connection = self.connection.clone();
+        tokio::task::spawn_blocking(move || query(&mut connection.lock().unwrap()))
+            .await?
+            .context("archive query failed")
+    }
+}
+
+/// A hash of everything about an event except its revision.
+fn content_hash(event: &Event) -> Result<String, serde_json::Error> {
+    let contents = serde_json::to_vec(&Event {
+        revision: None,
+        ..event.clone()
+    })?;
+    Ok(Sha256::digest(contents)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+fn from_seconds(seconds: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(seconds, 0).unwrap_or_default()
+}
+
+/// A time in a form that sorts in time order.
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
@@ -1,164 +1,754 @@
-use boa_engine::{js_str, js_string};
-use chrono::{DateTime, NaiveDateTime, Utc};
-use icalendar::{Calendar, Component, EventLike, EventStatus};
-use reqwest::Url;
+use std::collections::HashSet;
 
-use crate::{kent_schema, sums_pluto_schema};
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc};
+use chrono_tz::Europe::London;
+use reqwest::{Response, Url};
 
-pub(crate) async fn sums_calendar<T: Fn(&sums_pluto_schema::Event) -> String>(
+use crate::{
+    config::render_template,
+    description::Description,
+    drift::DriftReports,
+    gazetteer::Gazetteer,
+    kent_data::{self, ScriptRunner},
+    kent_schema,
+    source::{
+        Availability, CalendarSource, Campus, Contact, Event, Feed, FetchOptions, Image,
+        ImageDisplay, Place, Premium, Term, Tickets,
+    },
+    sums_pluto_schema,
+};
+
+/// A students' union site on the SUMS Pluto platform.
+pub(crate) struct SumsSource {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) site_id: String,
+    /// The SUMS Pluto events API.
+    pub(crate) api_url: String,
+    pub(crate) title: String,
+    pub(crate) description: String,
+    /// Template for each event's URL.
+    pub(crate) event_url: Option<String>,
+    /// Template for the page of the society or club running each event.
+    pub(crate) group_url: Option<String>,
+    pub(crate) gazetteer: Gazetteer,
+}
+
+#[async_trait::async_trait]
+impl CalendarSource for SumsSource {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn fetch(&self, options: &FetchOptions) -> Result<Feed, anyhow::Error> {
+        let mut events = sums_events(
+            &self.api_url,
+            &self.site_id,
+            self.event_url.as_deref(),
+            self.group_url.as_deref(),
+            options,
+        )
+        .await?;
+        locate(&self.gazetteer, &mut events);
+        Ok(Feed {
+            title: self.title.clone(),
+            description: self.description.clone(),
+            events,
+        })
+    }
+}
+
+/// An events listing page on one of the University of Kent websites.
+pub(crate) struct KentSource {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) url: String,
+    /// Overrides the title of the events page.
+    pub(crate) title: Option<String>,
+    /// Overrides the description of the events page.
+    pub(crate) description: Option<String>,
+    /// Template for each event's URL.
+    pub(crate) event_url: String,
+    /// Events that last longer than this many days are shown on their first
+    /// day only.
+    pub(crate) long_event_days: Option<u32>,
+    pub(crate) script_runner: ScriptRunner,
+    pub(crate) gazetteer: Gazetteer,
+    /// Where the differences from the expected schema are reported.
+    pub(crate) drift: DriftReports,
+}
+
+#[async_trait::async_trait]
+impl CalendarSource for KentSource {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn fetch(&self, _options: &FetchOptions) -> Result<Feed, anyhow::Error> {
+        let mut feed = kent_feed(self).await?;
+        locate(&self.gazetteer, &mut feed.events);
+        if let Some(title) = &self.title {
+            feed.title.clone_from(title);
+        }
+        if let Some(description) = &self.description {
+            feed.description.clone_from(description);
+        }
+        Ok(feed)
+    }
+}
+
+async fn sums_events(
+    api_url: &str,
     site_id: &str,
-    title: &str,
-    description: &str,
-    url_formatter: T,
-) -> Result<Calendar, anyhow::Error> {
-    let mut calendar = icalendar::Calendar::new();
-    calendar
-        .name(title)
-        .description(description)
-        .timezone("Europe/London");
-
-    let mut url = Url::parse_with_params(
-        "https://pluto.sums.su/api/events",
+    event_url: Option<&str>,
+    group_url: Option<&str>,
+    options: &FetchOptions,
+) -> Result<Vec<Event>, anyhow::Error> {
+    let mut query = Url::parse_with_params(
+        api_url,
         &[
             ("perPage", "40"),
             ("sortBy", "start_date"),
             ("futureOrOngoing", "1"),
-            ("onlyPremium", "1"),
         ],
     )?;
+    // The API doesn't document filters for categories, groups, venues or
+    // types, so those are applied to the fetched events instead.
+    if options.premium == Premium::Only {
+        query.query_pairs_mut().append_pair("onlyPremium", "1");
+    }
+    let mut url = query.clone();
 
+    let mut events = Vec::new();
     let client = reqwest::Client::new();
     loop {
-        let response = dbg!(
-            client
-                .get(url.clone())
-                .header("X-Site-Id", site_id)
-                .send()
-                .await?
-        )
-        .json::<sums_pluto_schema::Page>()
-        .await?;
+        let response = client
+            .get(url.clone())
+            .header("X-Site-Id", site_id)
+            .send()
+            .await?
+            .json::<sums_pluto_schema::Page>()
+            .await?;
 
         for event in response.data {
-            let mut cal_event = icalendar::Event::new();
-            cal_event
-                .summary(&event.title)
-                .description(&event.description)
-                .starts(
-                    DateTime::parse_from_rfc3339(&event.start_date)
-                        .unwrap()
-                        .with_timezone(&Utc),
-                )
-                .ends(
-                    DateTime::parse_from_rfc3339(&event.end_date)
-                        .unwrap()
-                        .with_timezone(&Utc),
+            let description = Description::from_html(&event.description);
+            let url = event_url.map(|template| {
+                render_template(
+                    template,
+                    &[
+                        ("id", &event.id.to_string()),
+                        ("event_id", &event.event_id.to_string()),
+                        ("url_name", event.url_name.as_deref().unwrap_or_default()),
+                    ],
                 )
-                .url(&url_formatter(&event))
-                .uid(&event.id.to_string());
-
-            if let Some(venue) = event.venue {
-                cal_event.location(&venue.name);
+            });
+            let mut calendar_event = Event {
+                uid: event.id.to_string(),
+                origin: format!("pluto.sums.su/{site_id}"),
+                title: event.title.clone(),
+                description: description.text,
+                description_html: Some(description.html).filter(|html| !html.is_empty()),
+                start: DateTime::parse_from_rfc3339(&event.start_date)?
+                    .with_timezone(&Utc)
+                    .into(),
+                end: DateTime::parse_from_rfc3339(&event.end_date)?
+                    .with_timezone(&Utc)
+                    .into(),
+                location: event.venue.as_ref().map(sums_location),
+                place: event.venue.as_ref().and_then(sums_place),
+                online: false,
+                join_url: None,
+                tickets: sums_tickets(&event, url.as_deref()),
+                url,
+                tentative: false,
+                cancelled: false,
+                campus: None,
+                categories: event
+                    .categories
+                    .iter()
+                    .map(|category| Term::new(category.id, &category.name))
+                    .collect(),
+                tags: Vec::new(),
+                group: event
+                    .group
+                    .as_ref()
+                    .map(|group| Term::new(group.id, group.name.as_deref().unwrap_or_default())),
+                venue: event
+                    .venue
+                    .as_ref()
+                    .map(|venue| Term::new(venue.id, &venue.name)),
+                event_type: Some(Term::new(event.datum_type.id, &event.datum_type.name)),
+                organizer: event
+                    .group
+                    .as_ref()
+                    .and_then(|group| sums_organizer(group, group_url)),
+                images: images(
+                    Some(&query),
+                    [
+                        (&event.image_url, ImageDisplay::Fullsize),
+                        (&event.thumbnail_url, ImageDisplay::Thumbnail),
+                        (&event.app_thumbnail_url, ImageDisplay::Thumbnail),
+                    ]
+                    .into_iter()
+                    .filter_map(|(url, display)| {
+                        Some((url.as_deref()?, display, Some(event.title.as_str())))
+                    }),
+                ),
+                updated: None,
+                revision: None,
+            };
+            if let Some(tickets) = &calendar_event.tickets {
+                let section = tickets.text();
+                calendar_event.add_note(&section);
             }
-            calendar.push(cal_event.done());
+            events.push(calendar_event);
         }
-        match response.next_page_url {
-            Some(next_url) => url = next_url.parse()?,
+        // Links to the next page leave out the filters, so only the page
+        // number is taken from them.
+        let next_page = response
+            .next_page_url
+            .map(|next_url| Url::parse(&next_url))
+            .transpose()?
+            .and_then(|next_url| {
+                next_url
+                    .query_pairs()
+                    .find(|(key, _)| key == "page")
+                    .map(|(_, page)| page.into_owned())
+            });
+        match next_page {
+            Some(page) => {
+                url = query.clone();
+                url.query_pairs_mut().append_pair("page", &page);
+            }
             None => break,
         }
     }
 
-    Ok(calendar)
+    Ok(events)
 }
 
-pub(crate) async fn kent_calendar(url: &str) -> Result<Calendar, anyhow::Error> {
-    let response = reqwest::get(url).await?;
-    let body = response.text().await?;
+async fn kent_feed(source: &KentSource) -> Result<Feed, anyhow::Error> {
+    let id = source.id.as_str();
+    let body = reqwest::get(&source.url)
+        .await
+        .and_then(Response::error_for_status)
+        .with_context(|| format!("failed to fetch {}", source.url))?
+        .text()
+        .await?;
 
-    let mut context = boa_engine::Context::default();
+    let (title, description, scripts) =
+        parse_kent_page(&body).with_context(|| format!("unexpected page at {}", source.url))?;
+    let (data, report) = kent_data::extract(scripts, &source.script_runner).await?;
+    source.drift.record(id, report);
 
-    let document = scraper::Html::parse_document(&body);
+    let assets_base_url =
+        Url::parse(&format!("{}/", data.assets_base_url.trim_end_matches('/'))).ok();
+    let mut events = Vec::new();
+    for event in data.events {
+        let parse = |time: &str| NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S");
+        let (Ok(start), Ok(end)) = (parse(&event.start), parse(&event.end)) else {
+            tracing::warn!(
+                source = id,
+                event = event.id,
+                "skipping Kent event without valid times"
+            );
+            continue;
+        };
+        let (location, join_url) = kent_location(&event);
+        let description = Description::from_html(&event.description);
+        let mut runs_until = None;
+        let (start, end) = match kent_days(event.all_day, start, end) {
+            Some((first, last))
+                if source
+                    .long_event_days
+                    .is_some_and(|days| (last - first).num_days() >= i64::from(days)) =>
+            {
+                runs_until = Some(format!("Runs until {}.", last.format("%A %-d %B %Y")));
+                (first.into(), first.succ_opt().unwrap_or(first).into())
+            }
+            Some((first, last)) => (first.into(), last.succ_opt().unwrap_or(last).into()),
+            None => (start.into(), end.into()),
+        };
+        let url = Some(render_template(
+            &source.event_url,
+            &[
+                ("events_base_url", &data.events_base_url),
+                ("id", &event.id.to_string()),
+                ("slug", &event.slug),
+            ],
+        ));
+        let mut calendar_event = Event {
+            uid: event.id.to_string(),
+            origin: "kent.ac.uk".to_owned(),
+            title: event.title.clone(),
+            description: description.text,
+            description_html: Some(description.html).filter(|html| !html.is_empty()),
+            start,
+            end,
+            location,
+            place: None,
+            online: event.online_event,
+            join_url,
+            // url: Some(event.url.clone()),
+            tickets: kent_tickets(&event, url.as_deref()),
+            url,
+            tentative: event.tentative,
+            cancelled: false,
+            campus: match event.campus_name {
+                kent_schema::EventCampus::Canterbury => Some(Campus::Canterbury),
+                kent_schema::EventCampus::Medway => Some(Campus::Medway),
+                kent_schema::EventCampus::Empty | kent_schema::EventCampus::Other(_) => None,
+            },
+            categories: kent_categories(&event),
+            tags: event.tags.iter().map(kent_term).collect(),
+            group: None,
+            venue: None,
+            event_type: None,
+            organizer: kent_contact(&event),
+            images: kent_images(&event, assets_base_url.as_ref()),
+            updated: event
+                .updated_at
+                .as_deref()
+                .and_then(|updated| parse(updated).ok())
+                .and_then(|updated| London.from_local_datetime(&updated).earliest())
+                .map(|updated| updated.to_utc()),
+            revision: None,
+        };
+        if let Some(note) = runs_until {
+            calendar_event.add_note(&note);
+        }
+        if let Some(tickets) = &calendar_event.tickets {
+            let section = tickets.text();
+            calendar_event.add_note(&section);
+        }
+        events.push(calendar_event);
+    }
+
+    Ok(Feed {
+        title,
+        description,
+        events,
+    })
+}
+
+/// The price of a Kent event, who can go, and whether it has to be booked.
+///
+/// Kent only describes these in words, so events are taken to need booking
+/// when their price or availability mentions booking, tickets or
+/// registration, unless it says they aren't needed. Free events open to
+/// everyone, with nothing else to say, have no tickets section.
+fn kent_tickets(event: &kent_schema::Event, event_url: Option<&str>) -> Option<Tickets> {
+    let field = |value: &str| Some(value.trim().to_owned()).filter(|value| !value.is_empty());
+    let price = field(&event.pricing);
+    let open_to = field(&event.open_to);
+    let notes = field(&event.availability);
+    let says = [&price, &notes]
+        .into_iter()
+        .flatten()
+        .fold(TicketWords::default(), |says, text| says.read(text));
+    let availability = if says.not_needed {
+        None
+    } else if says.sold_out {
+        Some(Availability::SoldOut)
+    } else if says.limited {
+        Some(Availability::Limited)
+    } else {
+        None
+    };
+    let bookable = !says.not_needed && (availability.is_some() || says.booking);
+    let is = |value: &Option<String>, usual: &str| {
+        value
+            .as_deref()
+            .is_none_or(|value| value.eq_ignore_ascii_case(usual))
+    };
+    if !bookable && notes.is_none() && is(&price, "free") && is(&open_to, "everyone") {
+        return None;
+    }
+    Some(Tickets {
+        price,
+        open_to,
+        notes,
+        url: event_url.filter(|_| bookable).map(str::to_owned),
+        availability,
+        bookable,
+    })
+}
+
+/// What Kent's words about tickets say, such as "£5, book online" or "No
+/// registration required".
+#[derive(Debug, Default)]
+struct TicketWords {
+    /// Booking, tickets or registration are mentioned.
+    booking: bool,
+    /// Booking is said not to be needed, such as "no need to register" or
+    /// "drop in".
+    not_needed: bool,
+    sold_out: bool,
+    limited: bool,
+}
+
+impl TicketWords {
+    /// Add what `text` says, a clause at a time, so that a "no" only applies
+    /// to the words near it.
+    fn read(mut self, text: &str) -> Self {
+        for clause in text
+            .to_lowercase()
+            .split([',', ';', '.', '!', '?', '(', ')', '\n'])
+        {
+            let words = clause
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|word| !word.is_empty())
+                .collect::<Vec<_>>();
+            let has = |options: &[&str]| words.iter().any(|word| options.contains(word));
+            let phrase = |first: &str, second: &str| {
+                words
+                    .windows(2)
+                    .any(|pair| pair[0] == first && pair[1] == second)
+            };
+            let booking = words.iter().any(|word| {
+                ["book", "ticket", "regist", "reserv"]
+                    .iter()
+                    .any(|stem| word.starts_with(stem))
+            });
+            let negated = has(&["no", "not", "without", "needn", "unnecessary"]);
+            let requirement = has(&[
+                "required",
+                "requirement",
+                "needed",
+                "need",
+                "necessary",
+                "unnecessary",
+                "needn",
+            ]);
+            let sold_out = phrase("sold", "out")
+                || phrase("fully", "booked")
+                || negated
+                    && !requirement
+                    && has(&[
+                        "left",
+                        "remaining",
+                        "available",
+                        "places",
+                        "spaces",
+                        "seats",
+                    ]);
+            self.booking |= booking;
+            self.not_needed |=
+                phrase("drop", "in") || negated && !sold_out && (booking || requirement);
+            self.sold_out |= sold_out;
+            self.limited |= has(&["limited", "few", "last"]);
+        }
+        self
+    }
+}
+
+/// Who to contact about a Kent event, if anyone is listed.
+fn kent_contact(event: &kent_schema::Event) -> Option<Contact> {
+    let field = |value: &str| Some(value.trim().to_owned()).filter(|value| !value.is_empty());
+    let contact = Contact {
+        name: event.contact_name.trim().to_owned(),
+        note: None,
+        email: field(&event.contact_email),
+        phone: field(&event.contact_phone),
+        url: None,
+    };
+    let listed = !contact.name.is_empty() || contact.email.is_some() || contact.phone.is_some();
+    listed.then_some(contact)
+}
+
+/// The banner and card images of a Kent event.
+fn kent_images(event: &kent_schema::Event, assets_base_url: Option<&Url>) -> Vec<Image> {
+    let banner = &event.banner_image;
+    let banner_alt = Some(banner.alt_text.as_str());
+    images(
+        assets_base_url,
+        [
+            (
+                banner.sizes.full.url.as_str(),
+                ImageDisplay::Fullsize,
+                banner_alt,
+            ),
+            (
+                banner.sizes.thumbnail.url.as_str(),
+                ImageDisplay::Thumbnail,
+                banner_alt,
+            ),
+            (
+                event.image.src.as_str(),
+                ImageDisplay::Graphic,
+                Some(event.image.alt.as_str()),
+            ),
+        ],
+    )
+}
+
+/// The images with a URL, with relative URLs resolved against `base`.
+///
+/// Only the first image with each URL is kept, and empty alt text is left
+/// out.
+fn images<'a>(
+    base: Option<&Url>,
+    candidates: impl IntoIterator<Item = (&'a str, ImageDisplay, Option<&'a str>)>,
+) -> Vec<Image> {
+    let mut images: Vec<Image> = Vec::new();
+    for (url, display, alt) in candidates {
+        let url = match (Url::parse(url), base) {
+            (Ok(url), _) => url,
+            (Err(_), Some(base)) if !url.trim().is_empty() => match base.join(url) {
+                Ok(url) => url,
+                Err(_) => continue,
+            },
+            (Err(_), _) => continue,
+        };
+        if images.iter().any(|image| image.url == url.as_str()) {
+            continue;
+        }
+        images.push(Image {
+            url: url.into(),
+            display,
+            alt: alt
+                .map(str::trim)
+                .filter(|alt| !alt.is_empty())
+                .map(str::to_owned),
+        });
+    }
+    images
+}
+
+/// The first and last days of an event that takes up whole days, because it's
+/// marked as all day or lasts for at least a day.
+///
+/// All day events that end at midnight are taken to end on the day before.
+fn kent_days(
+    all_day: bool,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> Option<(NaiveDate, NaiveDate)> {
+    let first = start.date();
+    if all_day {
+        let last = match end.date().pred_opt() {
+            Some(before) if end.time() == NaiveTime::MIN && before >= first => before,
+            _ => end.date(),
+        };
+        return Some((first, last.max(first)));
+    }
+    (end - start >= TimeDelta::days(1)).then(|| (first, end.date()))
+}
+
+/// Read the title, description and scripts from a Kent events page.
+fn parse_kent_page(body: &str) -> Result<(String, String, Vec<String>), anyhow::Error> {
+    let document = scraper::Html::parse_document(body);
     let description_selector =
         scraper::Selector::parse("head > meta[name=\"description\"]").unwrap();
     let title_selector = scraper::Selector::parse("head > title").unwrap();
+    // A page without a description can still list events, so only the
+    // title is required.
     let description = document
         .select(&description_selector)
         .next()
-        .unwrap()
-        .value()
-        .attr("content")
-        .unwrap();
+        .and_then(|meta| meta.value().attr("content"))
+        .unwrap_or_default();
     let title = document
         .select(&title_selector)
         .next()
-        .unwrap()
+        .context("page has no title")?
         .text()
         .collect::<String>();
 
-    let mut calendar = icalendar::Calendar::new();
-    calendar
-        .name(&title)
-        .description(description)
-        .timezone("Europe/London");
-
     let script_selector = scraper::Selector::parse("script").unwrap();
-    let script_elements = document.select(&script_selector);
-    context
-        .global_object()
-        .set(
-            js_string!("window"),
-            context.global_object(),
-            false,
-            &mut context,
-        )
-        .unwrap();
-
-    for element in script_elements {
-        // println!("{:?}", element);
-        // println!("{}", element.text().collect::<String>());
-        let _ = context
-            .eval(boa_engine::Source::from_bytes(
-                &element.text().collect::<String>(),
-            ))
-            .inspect_err(|e| {
-                eprintln!("{}", element.text().collect::<String>());
-                eprintln!("Uncaught {e}");
-            });
+    let scripts = document
+        .select(&script_selector)
+        .map(|element| element.text().collect::<String>())
+        .collect::<Vec<_>>();
+    Ok((title, description.to_owned(), scripts))
+}
+
+/// The categories of a Kent event, including its primary calendar.
+fn kent_categories(event: &kent_schema::Event) -> Vec<Term> {
+    let mut categories: Vec<Term> = std::iter::once(&event.primary_calendar)
+        .chain(&event.categories)
+        .map(kent_term)
+        .collect();
+    for name in &event.event_categories {
+        categories.push(Term::new(name, name));
     }
-    let data: kent_schema::Data = serde_json::from_value(
-        context
-            .global_object()
-            .get(js_str!("KENT"), &mut context)
-            .unwrap()
-            .to_json(&mut context)
-            .unwrap(),
-    )?;
+    let mut seen = HashSet::new();
+    categories.retain(|category| seen.insert(category.slug.clone()));
+    categories
+}
 
+fn kent_term(category: &kent_schema::PrimaryCalendar) -> Term {
+    Term {
+        id: category.id.to_string(),
+        name: category.name.clone(),
+        slug: category.slug.clone(),
+    }
+}
 
-    for event in data.events {
-        let mut cal_event = icalendar::Event::new();
-        cal_event
-            .summary(&event.title)
-            .description(&event.description)
-            .starts(NaiveDateTime::parse_from_str(&event.start, "%Y-%m-%d %H:%M:%S").unwrap())
-            .ends(NaiveDateTime::parse_from_str(&event.end, "%Y-%m-%d %H:%M:%S").unwrap())
-            .location(&event.location)
-            // .url(&event.url)
-            .url(
-                &(data.events_base_url.to_owned()
-                    + "/"
-                    + &event.id.to_string()
-                    + "/"
-                    + &event.slug),
-            )
-            .uid(&event.id.to_string());
-        // .all_day(event.all_day)
-        if event.tentative {
-            cal_event.status(EventStatus::Tentative);
-        }
+/// How to book a SUMS event, if it has to be booked.
+///
+/// Tickets are sold elsewhere or, if the event has products, on the union's
+/// own event page. The API doesn't say whether tickets have sold out, but
+/// products that can't be bought any more are hidden, so events whose
+/// products are all hidden are taken to be sold out.
+fn sums_tickets(event: &sums_pluto_schema::Event, event_url: Option<&str>) -> Option<Tickets> {
+    let external = event
+        .external_tickets
+        .as_deref()
+        .map(str::trim)
+        .filter(|url| url.starts_with("https://") || url.starts_with("http://"));
+    let has_products = event.has_products > 0 || event.product_count > 0;
+    if external.is_none() && !has_products {
+        return None;
+    }
+    let availability = match (event.product_count, event.hidden_product_count) {
+        (0, _) | (_, 0) => None,
+        (count, hidden) if hidden >= count => Some(Availability::SoldOut),
+        _ => Some(Availability::Limited),
+    };
+    Some(Tickets {
+        url: external.or(event_url).map(str::to_owned),
+        availability: if external.is_some() {
+            None
+        } else {
+            availability
+        },
+        bookable: true,
+        ..Tickets::default()
+    })
+}
 
-        calendar.push(cal_event.done());
+/// The society or club running a SUMS event.
+///
+/// Groups without a name are named after the group they're part of.
+fn sums_organizer(group: &sums_pluto_schema::Group, group_url: Option<&str>) -> Option<Contact> {
+    let name_of = |group: &sums_pluto_schema::Group| {
+        group
+            .name
+            .as_deref()
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_owned)
+    };
+    let parent = group.parent.as_deref().and_then(name_of);
+    let (name, parent) = match name_of(group) {
+        Some(name) => (name, parent),
+        None => (parent?, None),
+    };
+    let category = group.category.as_deref().and_then(name_of);
+    let note = match (category, parent) {
+        (Some(category), Some(parent)) => Some(format!("{category}, part of {parent}")),
+        (Some(category), None) => Some(category),
+        (None, Some(parent)) => Some(format!("part of {parent}")),
+        (None, None) => None,
+    };
+    Some(Contact {
+        name,
+        note,
+        email: None,
+        phone: None,
+        url: group_url.map(|template| render_template(template, &[("id", &group.id.to_string())])),
+    })
+}
+
+/// The name and address of a SUMS venue, as one line.
+fn sums_location(venue: &sums_pluto_schema::Venue) -> String {
+    std::iter::once(venue.name.trim())
+        .chain(sums_address(venue))
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A SUMS venue with an address, whose position may be in the gazetteer.
+fn sums_place(venue: &sums_pluto_schema::Venue) -> Option<Place> {
+    let address = sums_address(venue).collect::<Vec<_>>();
+    if address.is_empty() {
+        return None;
     }
+    Some(Place {
+        name: venue.name.trim().to_owned(),
+        address: Some(address.join(", ")),
+        geo: None,
+    })
+}
 
-    Ok(calendar)
+/// The parts of a SUMS venue's address that are filled in, leaving out any
+/// that repeat its name.
+fn sums_address(venue: &sums_pluto_schema::Venue) -> impl Iterator<Item = &str> {
+    [
+        &venue.address1,
+        &venue.address2,
+        &venue.address3,
+        &venue.address4,
+        &venue.postcode,
+        &venue.country,
+    ]
+    .into_iter()
+    .map(|part| part.trim())
+    .filter(|part| !part.is_empty() && !part.eq_ignore_ascii_case(venue.name.trim()))
+}
+
+/// Find the place each event's location refers to, and write the location
+/// under the place's name.
+///
+/// A location that only starts with a place's name, such as a room in a
+/// building, is kept as it is, and only takes the place's position. So do
+/// events whose source already gave a place.
+fn locate(gazetteer: &Gazetteer, events: &mut [Event]) {
+    for event in events {
+        let campus = event.campus;
+        if let Some(place) = &mut event.place {
+            if place.geo.is_none() {
+                place.geo = gazetteer
+                    .find(&place.name, campus)
+                    .or_else(|| gazetteer.find_start(&place.name, campus))
+                    .and_then(|known| known.geo);
+            }
+            continue;
+        }
+        let Some(location) = &event.location else {
+            continue;
+        };
+        if let Some(place) = gazetteer.find(location, campus) {
+            event.location = Some(place.name.clone());
+            event.place = Some(place.clone());
+        } else if let Some(place) = gazetteer.find_start(location, campus) {
+            event.place = Some(Place {
+                name: location.clone(),
+                ..place.clone()
+            });
+        }
+    }
+}
+
+/// Split a Kent event's location into a physical location and a link to
+/// join online.
+///
+/// Online events often give a URL, or just "Online", as their location, which
+/// shouldn't be shown as somewhere to go.
+fn kent_location(event: &kent_schema::Event) -> (Option<String>, Option<String>) {
+    let location = event.location.trim();
+    if location.is_empty() {
+        return (None, None);
+    }
+    if location.starts_with("https://") || location.starts_with("http://") {
+        return (None, Some(location.to_owned()));
+    }
+    let placeholder = ["online", "virtual", "zoom", "teams", "microsoft teams"]
+        .iter()
+        .any(|name| location.eq_ignore_ascii_case(name));
+    if event.online_event && placeholder {
+        return (None, None);
+    }
+    (Some(location.to_owned()), None)
 }
@@ -0,0 +1,354 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::{
+    archive::Archive,
+    calendars::{KentSource, SumsSource},
+    drift::DriftReports,
+    gazetteer::Gazetteer,
+    kent_data::ScriptRunner,
+    source::{CategoryNames, OutputOptions, Preset, Registration, UidFormat},
+    FeedCache,
+};
+
+/// The configuration used when no file is found.
+const DEFAULT_CONFIG: &str = include_str!("../config.toml");
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct Config {
+    #[serde(default = "default_host")]
+    pub(crate) host: String,
+    /// Default cache time to live, in seconds.
+    #[serde(default = "default_cache_ttl")]
+    pub(crate) cache_ttl: u64,
+    #[serde(default)]
+    pub(crate) sums: SumsConfig,
+    #[serde(default)]
+    pub(crate) scripts: ScriptConfig,
+    #[serde(skip)]
+    pub(crate) script_runner: ScriptRunner,
+    /// The gazetteer of places, instead of the built in one.
+    pub(crate) gazetteer: Option<String>,
+    #[serde(skip)]
+    pub(crate) places: Gazetteer,
+    #[serde(default)]
+    pub(crate) archive: ArchiveConfig,
+    #[serde(skip)]
+    pub(crate) event_archive: Option<Archive>,
+    #[serde(skip)]
+    pub(crate) feed_cache: FeedCache,
+    #[serde(skip)]
+    pub(crate) drift_reports: DriftReports,
+    /// How event UIDs are written, unless a calendar sets its own format.
+    #[serde(default)]
+    pub(crate) uid_format: UidFormat,
+    /// Attach event images, unless a calendar says otherwise.
+    #[serde(default)]
+    pub(crate) attach_images: bool,
+    /// Names to write categories under, shared by every calendar.
+    #[serde(default)]
+    pub(crate) categories: Arc<CategoryNames>,
+    #[serde(default)]
+    pub(crate) calendars: Vec<CalendarConfig>,
+}
+
+/// Settings for the `/sums/{site_id}.ics` endpoint.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct SumsConfig {
+    /// Serve any site id, not only the `sums` calendars configured by id.
+    pub(crate) any_site: bool,
+    /// The SUMS Pluto events API.
+    pub(crate) api_url: String,
+}
+
+impl Default for SumsConfig {
+    fn default() -> Self {
+        Self {
+            any_site: true,
+            api_url: "https://pluto.sums.su/api/events".to_owned(),
+        }
+    }
+}
+
+/// The budget for evaluating scripts on Kent pages, when the `KENT` data
+/// can't be read directly.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct ScriptConfig {
+    /// Wall-clock time allowed for evaluating a page's scripts, including
+    /// waiting for another page's evaluation to finish.
+    pub(crate) timeout_ms: u64,
+    pub(crate) loop_iteration_limit: u64,
+    pub(crate) recursion_limit: usize,
+    /// Maximum depth of the JavaScript engine's value stack.
+    pub(crate) stack_size_limit: usize,
+    /// Larger scripts are skipped.
+    pub(crate) max_script_bytes: usize,
+    /// Stack size of each evaluation thread, in bytes.
+    pub(crate) thread_stack_size: usize,
+    /// How many pages' scripts can be evaluated at once. Evaluations that
+    /// overrun their time budget still count until they stop.
+    pub(crate) max_concurrent: usize,
+}
+
+impl Default for ScriptConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 10_000,
+            loop_iteration_limit: 10_000_000,
+            recursion_limit: 512,
+            stack_size_limit: 1024,
+            max_script_bytes: 4 * 1024 * 1024,
+            thread_stack_size: 16 * 1024 * 1024,
+            max_concurrent: 2,
+        }
+    }
+}
+
+/// Where past events are kept.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct ArchiveConfig {
+    pub(crate) enabled: bool,
+    /// The SQLite database events are recorded in.
+    pub(crate) path: String,
+    /// How many days upcoming events are kept after upstream stops listing
+    /// them.
+    pub(crate) cancelled_grace_days: u32,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: "archive.sqlite".to_owned(),
+            cancelled_grace_days: 14,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct CalendarConfig {
+    pub(crate) id: String,
+    pub(crate) path: String,
+    pub(crate) name: String,
+    pub(crate) title: Option<String>,
+    pub(crate) description: Option<String>,
+    pub(crate) event_url: Option<String>,
+    /// Cache time to live, in seconds.
+    pub(crate) ttl: Option<u64>,
+    pub(crate) uid_format: Option<UidFormat>,
+    pub(crate) attach_images: Option<bool>,
+    /// Extra routes serving this calendar with filters applied.
+    #[serde(default)]
+    pub(crate) presets: Vec<PresetConfig>,
+    #[serde(flatten)]
+    pub(crate) source: SourceConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct PresetConfig {
+    pub(crate) path: String,
+    pub(crate) name: String,
+    /// Query string of filters, such as `campus=medway`.
+    pub(crate) query: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum SourceConfig {
+    Kent {
+        url: String,
+        /// Show events that last longer than this many days on their first
+        /// day only.
+        long_event_days: Option<u32>,
+    },
+    Sums {
+        site_id: String,
+        /// Template for the page of the society or club running each event.
+        group_url: Option<String>,
+    },
+}
+
+fn default_host() -> String {
+    "0.0.0.0:3779".to_owned()
+}
+
+fn default_cache_ttl() -> u64 {
+    60 * 60
+}
+
+impl Config {
+    /// Load the configuration file, then apply environment variable overrides.
+    pub(crate) fn load() -> Result<Self, anyhow::Error> {
+        let path = std::env::var("CONFIG_FILE").ok();
+        let mut config = match &path {
+            Some(path) => Self::from_file(path)?,
+            None if Path::new("config.toml").exists() => Self::from_file("config.toml")?,
+            None => Self::parse(DEFAULT_CONFIG)?,
+        };
+
+        if let Ok(host) = std::env::var("HOST") {
+            config.host = host;
+        }
+        if let Ok(ttl) = std::env::var("CACHE_TTL") {
+            config.cache_ttl = ttl
+                .parse()
+                .context("CACHE_TTL must be a number of seconds")?;
+        }
+
+        Ok(config)
+    }
+
+    fn from_file(path: &str) -> Result<Self, anyhow::Error> {
+        let contents =
+            std::fs::read_to_string(path).with_context(|| format!("failed to read {path}"))?;
+        Self::parse(&contents).with_context(|| format!("invalid configuration in {path}"))
+    }
+
+    pub(crate) fn parse(contents: &str) -> Result<Self, anyhow::Error> {
+        let mut config: Self = toml::from_str(contents)?;
+        config.script_runner = ScriptRunner::new(config.scripts.clone());
+        config.places = Gazetteer::load(config.gazetteer.as_deref())?;
+        if config.archive.enabled {
+            config.event_archive = Some(Archive::open(&config.archive)?);
+        }
+        Ok(config)
+    }
+
+    /// Build the registered calendar sources, followed by their presets.
+    pub(crate) fn registrations(&self) -> Result<Vec<Registration>, anyhow::Error> {
+        let mut registrations = Vec::new();
+        for calendar in &self.calendars {
+            let registration = calendar.registration(self);
+            let presets = calendar
+                .presets
+                .iter()
+                .map(|preset| {
+                    let query = serde_urlencoded::from_str(&preset.query)
+                        .with_context(|| format!("invalid query for preset {}", preset.path))?;
+                    Ok(Registration {
+                        path: preset.path.clone(),
+                        preset: Some(Preset {
+                            name: preset.name.clone(),
+                            query,
+                        }),
+                        ..registration.clone()
+                    })
+                })
+                .collect::<Result<Vec<_>, anyhow::Error>>()?;
+            registrations.push(registration);
+            registrations.extend(presets);
+        }
+        Ok(registrations)
+    }
+
+    /// How calendars are written, unless they set their own options.
+    pub(crate) fn output(&self) -> OutputOptions {
+        OutputOptions {
+            uid_format: self.uid_format,
+            attach_images: self.attach_images,
+            category_names: self.categories.clone(),
+        }
+    }
+
+    /// Find the calendar served at `/sums/{site}.ics`.
+    ///
+    /// `site` is either the id of a configured `sums` calendar, or, if
+    /// `any_site` is enabled, a raw SUMS site id.
+    pub(crate) fn sums_registration(&self, site: &str) -> Option<Registration> {
+        let alias = self.calendars.iter().find(|calendar| {
+            calendar.id == site && matches!(calendar.source, SourceConfig::Sums { .. })
+        });
+        if let Some(alias) = alias {
+            return Some(alias.registration(self));
+        }
+
+        let valid =
+            !site.is_empty() && site.len() <= 64 && site.chars().all(|c| c.is_ascii_alphanumeric());
+        if !self.sums.any_site || !valid {
+            return None;
+        }
+        Some(Registration {
+            path: format!("/sums/{site}.ics"),
+            ttl: Duration::from_secs(self.cache_ttl),
+            source: Arc::new(SumsSource {
+                id: format!("sums-{site}"),
+                name: format!("SUMS site {site}"),
+                site_id: site.to_owned(),
+                api_url: self.sums.api_url.clone(),
+                title: "Students' Union Calendar".to_owned(),
+                description: String::new(),
+                event_url: None,
+                group_url: None,
+                gazetteer: self.places.clone(),
+            }),
+            preset: None,
+            archive: self.event_archive.clone(),
+            cache: self.feed_cache.clone(),
+            output: self.output(),
+        })
+    }
+}
+
+impl CalendarConfig {
+    pub(crate) fn registration(&self, config: &Config) -> Registration {
+        let ttl = Duration::from_secs(self.ttl.unwrap_or(config.cache_ttl));
+        let source: Arc<dyn crate::source::CalendarSource> = match &self.source {
+            SourceConfig::Kent {
+                url,
+                long_event_days,
+            } => Arc::new(KentSource {
+                id: self.id.clone(),
+                name: self.name.clone(),
+                url: url.clone(),
+                title: self.title.clone(),
+                description: self.description.clone(),
+                event_url: self
+                    .event_url
+                    .clone()
+                    .unwrap_or_else(|| "{events_base_url}/{id}/{slug}".to_owned()),
+                long_event_days: *long_event_days,
+                script_runner: config.script_runner.clone(),
+                gazetteer: config.places.clone(),
+                drift: config.drift_reports.clone(),
+            }),
+            SourceConfig::Sums { site_id, group_url } => Arc::new(SumsSource {
+                id: self.id.clone(),
+                name: self.name.clone(),
+                site_id: site_id.clone(),
+                api_url: config.sums.api_url.clone(),
+                title: self.title.clone().unwrap_or_else(|| self.name.clone()),
+                description: self.description.clone().unwrap_or_default(),
+                event_url: self.event_url.clone(),
+                group_url: group_url.clone(),
+                gazetteer: config.places.clone(),
+            }),
+        };
+        Registration {
+            path: self.path.clone(),
+            ttl,
+            source,
+            preset: None,
+            archive: config.event_archive.clone(),
+            cache: config.feed_cache.clone(),
+            output: OutputOptions {
+                uid_format: self.uid_format.unwrap_or(config.uid_format),
+                attach_images: self.attach_images.unwrap_or(config.attach_images),
+                category_names: config.categories.clone(),
+            },
+        }
+    }
+}
+
+/// Substitute `{name}` placeholders in a URL template.
+pub(crate) fn render_template(template: &str, values: &[(&str, &str)]) -> String {
+    values
+        .iter()
+        .fold(template.to_owned(), |url, (name, value)| {
+            url.replace(&format!("{{{name}}}"), value)
+        })
+}
@@ -0,0 +1,320 @@
+use scraper::{ElementRef, Html, Node};
+
+use crate::escape_html;
+
+/// Elements whose contents are never shown.
+const HIDDEN: &[&str] = &[
+    "script", "style", "head", "template", "iframe", "object", "embed", "noscript", "form", "svg",
+    "img",
+];
+
+/// Elements kept in sanitized HTML. Other elements are replaced by their
+/// contents.
+const ALLOWED: &[&str] = &[
+    "p",
+    "br",
+    "a",
+    "strong",
+    "b",
+    "em",
+    "i",
+    "u",
+    "s",
+    "ul",
+    "ol",
+    "li",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "blockquote",
+    "pre",
+    "code",
+    "table",
+    "thead",
+    "tbody",
+    "tr",
+    "th",
+    "td",
+    "hr",
+];
+
+/// Elements that are written as a paragraph of their own.
+const PARAGRAPHS: &[&str] = &[
+    "p",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "blockquote",
+    "pre",
+    "table",
+    "ul",
+    "ol",
+    "hr",
+];
+
+/// Elements that start on a new line.
+const LINES: &[&str] = &[
+    "div", "section", "article", "header", "footer", "li", "tr", "dt", "dd",
+];
+
+/// An event description from upstream, which is HTML.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Description {
+    /// Readable plain text, with links listed as numbered footnotes.
+    pub(crate) text: String,
+    /// The HTML with only basic formatting and links kept.
+    pub(crate) html: String,
+}
+
+impl Description {
+    pub(crate) fn from_html(html: &str) -> Self {
+        let fragment = Html::parse_fragment(html);
+        let root = fragment.root_element();
+
+        let mut text = TextWriter::default();
+        text.children(root);
+        let mut sanitized = String::new();
+        sanitize_children(root, &mut sanitized);
+
+        Self {
+            text: text.finish(),
+            html: sanitized.trim().to_owned(),
+        }
+    }
+}
+
+/// Builds the plain text of a description.
+#[derive(Default)]
+struct TextWriter {
+    text: String,
+    /// Newlines owed before the next text, so that blocks are separated
+    /// without leaving blank lines at the end.
+    pending_newlines: usize,
+    /// Whether a space is owed before the next text.
+    pending_space: bool,
+    /// The kind and count of each list the writer is in.
+    lists: Vec<(bool, usize)>,
+    /// Whether a list item's marker was the last thing written, so that a
+    /// paragraph inside the item starts on the same line.
+    item_start: bool,
+    /// Whitespace is kept inside `pre`.
+    preformatted: usize,
+    links: Vec<String>,
+}
+
+impl TextWriter {
+    fn children(&mut self, element: ElementRef) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => self.text(text),
+                Node::Element(_) => {
+                    if let Some(child) = ElementRef::wrap(child) {
+                        self.element(child);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn element(&mut self, element: ElementRef) {
+        let name = element.value().name();
+        if HIDDEN.contains(&name) {
+            return;
+        }
+        let block = if matches!(name, "ul" | "ol") && !self.lists.is_empty() {
+            1
+        } else if PARAGRAPHS.contains(&name) {
+            2
+        } else if LINES.contains(&name) {
+            1
+        } else {
+            0
+        };
+        self.block(block);
+
+        match name {
+            "br" => self.line_break(),
+            "ul" | "ol" => {
+                self.lists.push((name == "ol", 0));
+                self.children(element);
+                self.lists.pop();
+            }
+            "li" => {
+                let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+                let marker = match self.lists.last_mut() {
+                    Some((true, count)) => {
+                        *count += 1;
+                        format!("{count}. ")
+                    }
+                    _ => "• ".to_owned(),
+                };
+                self.raw(&format!("{indent}{marker}"));
+                self.item_start = true;
+                self.children(element);
+                // Items are on consecutive lines, even if they hold paragraphs.
+                self.pending_newlines = self.pending_newlines.min(1);
+            }
+            "a" => {
+                let before = self.text.len();
+                self.children(element);
+                let shown = self.text[before..].trim().to_owned();
+                if let Some(href) = element.value().attr("href").and_then(safe_link) {
+                    // Links whose text is their address don't need a note.
+                    let address = href.strip_prefix("mailto:").unwrap_or(href);
+                    if shown != href && shown != address {
+                        let number = self.footnote(href);
+                        self.raw(&format!(" [{number}]"));
+                    }
+                }
+            }
+            "pre" => {
+                self.preformatted += 1;
+                self.children(element);
+                self.preformatted -= 1;
+            }
+            "td" | "th" => {
+                self.pending_space = true;
+                self.children(element);
+                self.pending_space = true;
+            }
+            _ => self.children(element),
+        }
+
+        self.block(block);
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.preformatted > 0 {
+            for (index, line) in text.split('\n').enumerate() {
+                if index > 0 {
+                    self.line_break();
+                }
+                if !line.is_empty() {
+                    self.raw(line);
+                }
+            }
+            return;
+        }
+        if text.starts_with(char::is_whitespace) {
+            self.pending_space = true;
+        }
+        let mut words = text.split_whitespace().peekable();
+        while let Some(word) = words.next() {
+            self.raw(word);
+            if words.peek().is_some() {
+                self.pending_space = true;
+            }
+        }
+        if text.ends_with(char::is_whitespace) {
+            self.pending_space = true;
+        }
+    }
+
+    /// Write text as it is, after any owed separators.
+    fn raw(&mut self, text: &str) {
+        if self.text.is_empty() {
+            self.pending_newlines = 0;
+            self.pending_space = false;
+        } else if self.pending_newlines > 0 {
+            self.text.push_str(&"\n".repeat(self.pending_newlines));
+        } else if self.pending_space && !self.text.ends_with([' ', '\n']) {
+            self.text.push(' ');
+        }
+        self.pending_newlines = 0;
+        self.pending_space = false;
+        self.item_start = false;
+        self.text.push_str(text);
+    }
+
+    /// Make sure the next text is at least `newlines` lines further down.
+    fn block(&mut self, newlines: usize) {
+        if newlines > 0 && !self.text.is_empty() && !self.item_start {
+            self.pending_newlines = self.pending_newlines.max(newlines);
+            self.pending_space = false;
+        }
+    }
+
+    fn line_break(&mut self) {
+        if !self.text.is_empty() {
+            self.pending_newlines += 1;
+            self.pending_space = false;
+        }
+    }
+
+    /// The number of the footnote for a link, adding it if it's new.
+    fn footnote(&mut self, href: &str) -> usize {
+        match self.links.iter().position(|link| link == href) {
+            Some(index) => index + 1,
+            None => {
+                self.links.push(href.to_owned());
+                self.links.len()
+            }
+        }
+    }
+
+    fn finish(mut self) -> String {
+        let mut text = std::mem::take(&mut self.text);
+        if !self.links.is_empty() {
+            text.push_str("\n\nLinks:");
+            for (index, link) in self.links.iter().enumerate() {
+                text.push_str(&format!("\n[{}] {link}", index + 1));
+            }
+        }
+        text.trim().to_owned()
+    }
+}
+
+fn sanitize_children(element: ElementRef, out: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => out.push_str(&escape_html(text)),
+            Node::Element(_) => {
+                if let Some(child) = ElementRef::wrap(child) {
+                    sanitize_element(child, out);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn sanitize_element(element: ElementRef, out: &mut String) {
+    let name = element.value().name();
+    if HIDDEN.contains(&name) {
+        return;
+    }
+    if !ALLOWED.contains(&name) {
+        sanitize_children(element, out);
+        return;
+    }
+    match name {
+        "br" | "hr" => {
+            out.push_str(&format!("<{name}>"));
+            return;
+        }
+        "a" => match element.value().attr("href").and_then(safe_link) {
+            Some(href) => out.push_str(&format!("<a href=\"{}\">", escape_html(href))),
+            None => {
+                sanitize_children(element, out);
+                return;
+            }
+        },
+        _ => out.push_str(&format!("<{name}>")),
+    }
+    sanitize_children(element, out);
+    out.push_str(&format!("</{name}>"));
+}
+
+/// A link that's safe to keep, which is any web, email or phone link.
+fn safe_link(href: &str) -> Option<&str> {
+    let href = href.trim();
+    let scheme = href.split_once(':')?.0.to_ascii_lowercase();
+    matches!(scheme.as_str(), "http" | "https" | "mailto" | "tel").then_some(href)
+}
@@ -0,0 +1,170 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::kent_schema;
+
+/// Differences between the data on a Kent page and [`kent_schema`].
+///
+/// Fields are named by their path in the data, with `[]` for each element of
+/// a list, and counted once for every object they're missing from or
+/// unexpectedly found in.
+#[derive(Debug, Clone, Default, Serialize)]
+pub(crate) struct DriftReport {
+    checked_at: Option<DateTime<Utc>>,
+    /// Required fields in the schema that were missing or null.
+    missing_fields: BTreeMap<String, usize>,
+    /// Fields that aren't in the schema.
+    unknown_fields: BTreeMap<String, usize>,
+    /// Values of enums that aren't in the schema.
+    unknown_values: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl DriftReport {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.missing_fields.is_empty()
+            && self.unknown_fields.is_empty()
+            && self.unknown_values.is_empty()
+    }
+
+    /// Compare the fields of the `KENT` data object with the schema.
+    ///
+    /// Null fields are removed, so that they take their default value.
+    pub(crate) fn check_fields(&mut self, data: &mut Value) {
+        let Value::Object(data) = data else {
+            return;
+        };
+        self.compare("", &template(&kent_schema::Data::default()), data);
+
+        let event = template(&kent_schema::Event::default());
+        let term = template(&kent_schema::PrimaryCalendar::default());
+        for event_data in objects(data.get_mut("events")) {
+            self.compare("events[].", &event, event_data);
+            for field in ["categories", "tags"] {
+                for term_data in objects(event_data.get_mut(field)) {
+                    self.compare(&format!("events[].{field}[]."), &term, term_data);
+                }
+            }
+        }
+    }
+
+    /// Record the enum values in `data` that aren't in the schema.
+    pub(crate) fn check_values(&mut self, data: &kent_schema::Data) {
+        for campus in &data.event_campuses {
+            self.unknown_value("event_campuses[]", campus.unknown());
+        }
+        for event in &data.events {
+            self.unknown_value("events[].campus_name", event.campus_name.unknown());
+            self.unknown_value("events[].country", event.country.unknown());
+            self.unknown_value("events[].start_month", event.start_month.unknown());
+            self.unknown_value("events[].end_month", event.end_month.unknown());
+            self.unknown_value(
+                "events[].start_month_full",
+                event.start_month_full.unknown(),
+            );
+            self.unknown_value("events[].end_month_full", event.end_month_full.unknown());
+            self.unknown_value(
+                "events[].banner_image.focus",
+                event.banner_image.focus.unknown(),
+            );
+        }
+    }
+
+    /// Compare the fields of `actual` with those of `expected`, recursing
+    /// into nested objects.
+    ///
+    /// An empty `expected` object is a map with arbitrary keys, so isn't
+    /// compared.
+    fn compare(
+        &mut self,
+        prefix: &str,
+        expected: &Map<String, Value>,
+        actual: &mut Map<String, Value>,
+    ) {
+        if expected.is_empty() {
+            return;
+        }
+        for (key, expected_value) in expected {
+            let path = format!("{prefix}{key}");
+            match actual.get_mut(key) {
+                // Optional fields are null in the template.
+                None if expected_value.is_null() => {}
+                None => *self.missing_fields.entry(path).or_default() += 1,
+                Some(Value::Null) if !expected_value.is_null() => {
+                    actual.remove(key);
+                    *self.missing_fields.entry(path).or_default() += 1;
+                }
+                Some(Value::Object(actual_value)) => {
+                    if let Value::Object(expected_value) = expected_value {
+                        self.compare(&format!("{path}."), expected_value, actual_value);
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+        for key in actual.keys() {
+            if !expected.contains_key(key) {
+                *self
+                    .unknown_fields
+                    .entry(format!("{prefix}{key}"))
+                    .or_default() += 1;
+            }
+        }
+    }
+
+    fn unknown_value(&mut self, path: &str, value: Option<&str>) {
+        if let Some(value) = value {
+            self.unknown_values
+                .entry(path.to_owned())
+                .or_default()
+                .insert(value.to_owned());
+        }
+    }
+}
+
+/// The fields of a default value of a schema type.
+fn template(value: &impl Serialize) -> Map<String, Value> {
+    match serde_json::to_value(value) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    }
+}
+
+/// The objects in a list.
+fn objects(list: Option<&mut Value>) -> impl Iterator<Item = &mut Map<String, Value>> {
+    list.and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object_mut)
+}
+
+/// The latest report for each Kent source, by source id.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DriftReports(Arc<Mutex<BTreeMap<String, DriftReport>>>);
+
+impl DriftReports {
+    /// Log and keep the report for a source, replacing its previous report.
+    pub(crate) fn record(&self, source: &str, mut report: DriftReport) {
+        if !report.is_empty() {
+            tracing::warn!(
+                source,
+                missing_fields = ?report.missing_fields,
+                unknown_fields = ?report.unknown_fields,
+                unknown_values = ?report.unknown_values,
+                "Kent data has drifted from the schema"
+            );
+        }
+        report.checked_at = Some(Utc::now());
+        self.0.lock().unwrap().insert(source.to_owned(), report);
+    }
+
+    /// The latest report for each source that has been fetched.
+    pub(crate) fn all(&self) -> BTreeMap<String, DriftReport> {
+        self.0.lock().unwrap().clone()
+    }
+}
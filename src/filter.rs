@@ -0,0 +1,164 @@
+use anyhow::bail;
+use chrono::TimeDelta;
+
+use crate::source::{Campus, Event, Feed, FetchOptions, Mode, Premium, Term};
+
+/// Filters selected with query parameters on a calendar route.
+///
+/// Parameters may be repeated, and an event is kept if it matches any of the
+/// values given for a parameter. Values match a term's id, name or slug, and
+/// a value prefixed with `-` excludes the events it matches instead.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Filter {
+    categories: Terms,
+    tags: Terms,
+    groups: Terms,
+    venues: Terms,
+    types: Terms,
+    /// Events without a campus are kept whichever campus is chosen.
+    campus: Option<Campus>,
+    mode: Option<Mode>,
+    /// Whether events have to be booked in advance.
+    bookable: Option<bool>,
+    /// Options that are passed through to the upstream.
+    pub(crate) options: FetchOptions,
+    /// How far back to include archived events that upstream no longer lists.
+    pub(crate) history: Option<TimeDelta>,
+}
+
+impl Filter {
+    /// Parse the filters from a route's query parameters.
+    ///
+    /// Unknown parameters are ignored.
+    pub(crate) fn from_query(params: &[(String, String)]) -> Result<Self, anyhow::Error> {
+        let mut filter = Filter::default();
+        for (key, value) in params {
+            match key.as_str() {
+                "category" => filter.categories.push(value),
+                "tag" => filter.tags.push(value),
+                "group" => filter.groups.push(value),
+                "venue" => filter.venues.push(value),
+                "type" => filter.types.push(value),
+                "campus" => {
+                    filter.campus = match value.as_str() {
+                        "canterbury" => Some(Campus::Canterbury),
+                        "medway" => Some(Campus::Medway),
+                        "any" => None,
+                        _ => bail!("campus must be `canterbury`, `medway` or `any`"),
+                    }
+                }
+                "mode" => {
+                    filter.mode = match value.as_str() {
+                        "online" => Some(Mode::Online),
+                        "in-person" => Some(Mode::InPerson),
+                        "any" => None,
+                        _ => bail!("mode must be `online`, `in-person` or `any`"),
+                    }
+                }
+                "bookable" => {
+                    filter.bookable = match value.as_str() {
+                        "true" => Some(true),
+                        "false" => Some(false),
+                        "any" => None,
+                        _ => bail!("bookable must be `true`, `false` or `any`"),
+                    }
+                }
+                "premium" => {
+                    filter.options.premium = match value.as_str() {
+                        "only" => Premium::Only,
+                        "all" => Premium::All,
+                        _ => bail!("premium must be `only` or `all`"),
+                    }
+                }
+                "history" => filter.history = Some(parse_history(value)?),
+                _ => {}
+            }
+        }
+        Ok(filter)
+    }
+
+    pub(crate) fn matches(&self, event: &Event) -> bool {
+        self.categories.matches(&event.categories)
+            && self.tags.matches(&event.tags)
+            && self.groups.matches(event.group.as_slice())
+            && self.venues.matches(event.venue.as_slice())
+            && self.types.matches(event.event_type.as_slice())
+            && self
+                .campus
+                .zip(event.campus)
+                .is_none_or(|(campus, event_campus)| campus == event_campus)
+            && self.mode.is_none_or(|mode| match mode {
+                Mode::Online => event.online,
+                Mode::InPerson => !event.online,
+            })
+            && self.bookable.is_none_or(|bookable| {
+                event
+                    .tickets
+                    .as_ref()
+                    .is_some_and(|tickets| tickets.bookable)
+                    == bookable
+            })
+    }
+
+    /// Copy of `feed` with only the matching events.
+    pub(crate) fn apply(&self, feed: &Feed) -> Feed {
+        Feed {
+            title: feed.title.clone(),
+            description: feed.description.clone(),
+            events: feed
+                .events
+                .iter()
+                .filter(|event| self.matches(event))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// The longest `history` that can be asked for, in days, which is about a
+/// hundred years.
+const MAX_HISTORY_DAYS: i64 = 36_525;
+
+/// Parse a period such as `90d` or `12w`.
+fn parse_history(value: &str) -> Result<TimeDelta, anyhow::Error> {
+    let count = |value: &str| value.parse::<u32>().ok().map(i64::from);
+    let period = match (value.strip_suffix('d'), value.strip_suffix('w')) {
+        (Some(days), _) => count(days).and_then(TimeDelta::try_days),
+        (_, Some(weeks)) => count(weeks).and_then(TimeDelta::try_weeks),
+        _ => None,
+    };
+    match period {
+        Some(period) if period.num_days() > MAX_HISTORY_DAYS => {
+            bail!("history can be at most {MAX_HISTORY_DAYS} days")
+        }
+        Some(period) => Ok(period),
+        None => bail!("history must be a number of days or weeks, such as `90d` or `12w`"),
+    }
+}
+
+/// The values given for one term parameter.
+#[derive(Debug, Clone, Default)]
+struct Terms {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl Terms {
+    fn push(&mut self, value: &str) {
+        match value.strip_prefix('-') {
+            Some(value) => self.exclude.push(value.to_owned()),
+            None => self.include.push(value.to_owned()),
+        }
+    }
+
+    /// Whether `terms` has any included value, or there are none to include,
+    /// and has no excluded value.
+    fn matches(&self, terms: &[Term]) -> bool {
+        let any = |values: &[String]| {
+            values
+                .iter()
+                .any(|value| terms.iter().any(|term| term.matches(value)))
+        };
+        (self.include.is_empty() || any(&self.include)) && !any(&self.exclude)
+    }
+}
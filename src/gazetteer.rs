@@ -0,0 +1,114 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{bail, Context};
+use serde::Deserialize;
+
+use crate::source::{Campus, Geo, Place};
+
+/// The gazetteer used when the configuration doesn't name one.
+const DEFAULT_GAZETTEER: &str = include_str!("../gazetteer.toml");
+
+/// The buildings and venues events are held at, by every name they're
+/// written under.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Gazetteer {
+    /// Each place, and the campus it's on if it's on one.
+    places: Arc<Vec<(Place, Option<Campus>)>>,
+    /// The index of each place, by its normalized name and aliases.
+    names: Arc<HashMap<String, usize>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GazetteerFile {
+    #[serde(default)]
+    places: Vec<Entry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Entry {
+    name: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+    address: Option<String>,
+    campus: Option<Campus>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+}
+
+impl Gazetteer {
+    /// Load the gazetteer at `path`, or the built in one.
+    pub(crate) fn load(path: Option<&str>) -> Result<Self, anyhow::Error> {
+        let Some(path) = path else {
+            return Self::parse(DEFAULT_GAZETTEER);
+        };
+        let contents =
+            std::fs::read_to_string(path).with_context(|| format!("failed to read {path}"))?;
+        Self::parse(&contents).with_context(|| format!("invalid gazetteer in {path}"))
+    }
+
+    pub(crate) fn parse(contents: &str) -> Result<Self, anyhow::Error> {
+        let file: GazetteerFile = toml::from_str(contents)?;
+        let mut places = Vec::new();
+        let mut names = HashMap::new();
+        for entry in file.places {
+            let geo = match (entry.latitude, entry.longitude) {
+                (Some(latitude), Some(longitude)) => Some(Geo {
+                    latitude,
+                    longitude,
+                }),
+                (None, None) => None,
+                _ => bail!("{} needs both a latitude and a longitude", entry.name),
+            };
+            for name in std::iter::once(&entry.name).chain(&entry.aliases) {
+                let index = places.len();
+                if let Some(other) = names.insert(normalize(name), index) {
+                    if other != index {
+                        let (other, _): &(Place, _) = &places[other];
+                        bail!("{name} names both {} and {}", other.name, entry.name);
+                    }
+                }
+            }
+            places.push((
+                Place {
+                    name: entry.name,
+                    address: entry.address,
+                    geo,
+                },
+                entry.campus,
+            ));
+        }
+        Ok(Self {
+            places: Arc::new(places),
+            names: Arc::new(names),
+        })
+    }
+
+    /// The place a location names, if it's in the gazetteer.
+    ///
+    /// Places on a campus are only found for events on that campus, or not
+    /// tied to either.
+    pub(crate) fn find(&self, location: &str, campus: Option<Campus>) -> Option<&Place> {
+        let (place, place_campus) = self.places.get(*self.names.get(&normalize(location))?)?;
+        match (campus, place_campus) {
+            (Some(campus), Some(place_campus)) if campus != *place_campus => None,
+            _ => Some(place),
+        }
+    }
+
+    /// The place the part of a location before its first comma names, such
+    /// as the building in "Rutherford College, Seminar Room 5".
+    pub(crate) fn find_start(&self, location: &str, campus: Option<Campus>) -> Option<&Place> {
+        let (first, _) = location.split_once(',')?;
+        self.find(first, campus)
+    }
+}
+
+/// A name in lower case, with each run of punctuation and spaces replaced by
+/// a single space.
+fn normalize(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,307 @@
+use std::{
+    sync::{
+        atomic::{AtomicU8, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail};
+use boa_engine::{js_str, js_string};
+use tokio::sync::{oneshot, Semaphore};
+
+use crate::{config::ScriptConfig, drift::DriftReport, kent_schema};
+
+/// Runs the scripts from Kent pages, within a budget.
+///
+/// Each evaluation runs outside the async runtime, on a new thread, and at
+/// most `max_concurrent` run at once. Waiting for a turn counts against the
+/// time budget. An evaluation that overruns the budget is abandoned, but a
+/// thread can't be stopped, so it keeps its turn until the engine's runtime
+/// limits stop it, which may be never.
+#[derive(Debug, Clone)]
+pub(crate) struct ScriptRunner {
+    config: ScriptConfig,
+    permits: Arc<Semaphore>,
+    /// How many abandoned evaluations are still running.
+    abandoned: Arc<AtomicUsize>,
+}
+
+impl ScriptRunner {
+    pub(crate) fn new(config: ScriptConfig) -> Self {
+        let permits = Arc::new(Semaphore::new(config.max_concurrent));
+        Self {
+            config,
+            permits,
+            abandoned: Arc::default(),
+        }
+    }
+}
+
+impl Default for ScriptRunner {
+    fn default() -> Self {
+        Self::new(ScriptConfig::default())
+    }
+}
+
+/// Extract the `KENT` data object from the scripts on a Kent events page.
+///
+/// The object is usually assigned as a literal, which is read directly from
+/// the script text. If that fails, the scripts are evaluated and the object is
+/// read from the resulting global scope, which is much slower.
+///
+/// Alongside the data is a report of how it differs from the schema.
+pub(crate) async fn extract(
+    scripts: Vec<String>,
+    runner: &ScriptRunner,
+) -> Result<(kent_schema::Data, DriftReport), anyhow::Error> {
+    for script in &scripts {
+        let Some(literal) = assigned_literal(script) else {
+            continue;
+        };
+        let value = serde_json::from_str::<serde_json::Value>(literal)
+            .or_else(|_| json5::from_str::<serde_json::Value>(literal));
+        match value.map(read) {
+            Ok(Ok(data)) => {
+                tracing::info!(extraction = "literal", "extracted KENT data");
+                return Ok(data);
+            }
+            Ok(Err(e)) => tracing::warn!("KENT literal does not match the schema: {e}"),
+            Err(e) => tracing::warn!("KENT literal could not be parsed: {e}"),
+        }
+    }
+
+    tracing::info!(
+        extraction = "evaluation",
+        "extracting KENT data by evaluating scripts"
+    );
+    let timeout_ms = runner.config.timeout_ms;
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+    let Ok(permit) =
+        tokio::time::timeout_at(deadline.into(), runner.permits.clone().acquire_owned()).await
+    else {
+        tracing::warn!(
+            timeout_ms,
+            abandoned = runner.abandoned.load(Ordering::SeqCst),
+            "no script evaluation could start within the time budget"
+        );
+        bail!("script evaluation exceeded its time budget")
+    };
+    let permit = permit?;
+    let config = runner.config.clone();
+    let state = Arc::new(AtomicU8::new(RUNNING));
+    let running = Running {
+        state: state.clone(),
+        abandoned: runner.abandoned.clone(),
+    };
+    let (sender, receiver) = oneshot::channel();
+    std::thread::Builder::new()
+        .name("kent-script".to_owned())
+        .stack_size(config.thread_stack_size)
+        .spawn(move || {
+            let running = running;
+            let result = evaluate(&scripts, &config, deadline);
+            drop(permit);
+            drop(running);
+            let _ = sender.send(result);
+        })?;
+
+    let _waiting = Waiting {
+        state,
+        abandoned: runner.abandoned.clone(),
+    };
+    match tokio::time::timeout_at(deadline.into(), receiver).await {
+        Ok(result) => result.map_err(|_| anyhow!("script evaluation thread panicked"))?,
+        Err(_) => {
+            tracing::warn!(timeout_ms, "script evaluation exceeded its time budget");
+            bail!("script evaluation exceeded its time budget")
+        }
+    }
+}
+
+/// The states of an evaluation.
+const RUNNING: u8 = 0;
+const FINISHED: u8 = 1;
+const ABANDONED: u8 = 2;
+
+/// Held by whoever is waiting for an evaluation. If they stop waiting before
+/// it finishes, because it overran its budget or the request was cancelled,
+/// the evaluation counts as abandoned until its thread finishes.
+struct Waiting {
+    state: Arc<AtomicU8>,
+    abandoned: Arc<AtomicUsize>,
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        // Counted first, so that the thread can't finish and uncount itself
+        // before it's counted.
+        let abandoned = self.abandoned.fetch_add(1, Ordering::SeqCst) + 1;
+        let state =
+            self.state
+                .compare_exchange(RUNNING, ABANDONED, Ordering::SeqCst, Ordering::SeqCst);
+        if state.is_ok() {
+            tracing::warn!(
+                abandoned,
+                "abandoned a script evaluation that is still running"
+            );
+        } else {
+            self.abandoned.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Held by the thread running an evaluation, until it finishes or panics.
+struct Running {
+    state: Arc<AtomicU8>,
+    abandoned: Arc<AtomicUsize>,
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        let state =
+            self.state
+                .compare_exchange(RUNNING, FINISHED, Ordering::SeqCst, Ordering::SeqCst);
+        if state.is_err() {
+            let abandoned = self.abandoned.fetch_sub(1, Ordering::SeqCst) - 1;
+            tracing::info!(abandoned, "abandoned script evaluation finished");
+        }
+    }
+}
+
+/// Deserialize the `KENT` data object, reporting how it differs from the
+/// schema.
+fn read(
+    mut value: serde_json::Value,
+) -> Result<(kent_schema::Data, DriftReport), serde_json::Error> {
+    let mut report = DriftReport::default();
+    report.check_fields(&mut value);
+    let data = serde_json::from_value(value)?;
+    report.check_values(&data);
+    Ok((data, report))
+}
+
+/// Find an object literal assigned to `KENT` or `window.KENT` in a script.
+pub(crate) fn assigned_literal(script: &str) -> Option<&str> {
+    let mut rest = script;
+    while let Some(index) = rest.find("KENT") {
+        let before = &rest[..index];
+        let after = rest[index + "KENT".len()..].trim_start();
+        rest = &rest[index + "KENT".len()..];
+
+        // Skip identifiers that merely contain `KENT`, such as `KENT_CONFIG`.
+        let preceding = before.chars().next_back();
+        if preceding.is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '$') {
+            continue;
+        }
+        if preceding == Some('.') && !before.ends_with("window.") {
+            continue;
+        }
+        let Some(value) = after.strip_prefix('=') else {
+            continue;
+        };
+        if value.starts_with('=') {
+            continue;
+        }
+        let value = value.trim_start();
+        if value.starts_with('{') {
+            return object_literal(value);
+        }
+    }
+    None
+}
+
+/// The object literal at the start of `source`, up to its matching brace.
+fn object_literal(source: &str) -> Option<&str> {
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, c) in source.char_indices() {
+        if let Some(q) = quote {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                _ if c == q => quote = None,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' | '`' => quote = Some(c),
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&source[..=index]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Evaluate the scripts in a JavaScript engine, and read `KENT` from the
+/// global scope.
+///
+/// Scripts that hit the runtime limits are reported and skipped, as are any
+/// left once `deadline` has passed.
+fn evaluate(
+    scripts: &[String],
+    config: &ScriptConfig,
+    deadline: Instant,
+) -> Result<(kent_schema::Data, DriftReport), anyhow::Error> {
+    let mut context = boa_engine::Context::default();
+    let limits = context.runtime_limits_mut();
+    limits.set_loop_iteration_limit(config.loop_iteration_limit);
+    limits.set_recursion_limit(config.recursion_limit);
+    limits.set_stack_size_limit(config.stack_size_limit);
+    context
+        .global_object()
+        .set(
+            js_string!("window"),
+            context.global_object(),
+            false,
+            &mut context,
+        )
+        .unwrap();
+
+    for (index, script) in scripts.iter().enumerate() {
+        if Instant::now() > deadline {
+            tracing::warn!(
+                skipped = scripts.len() - index,
+                "script evaluation ran out of time, skipping remaining scripts"
+            );
+            break;
+        }
+        if script.len() > config.max_script_bytes {
+            tracing::warn!(
+                index,
+                bytes = script.len(),
+                "skipping script larger than the size limit"
+            );
+            continue;
+        }
+        let started = Instant::now();
+        match context.eval(boa_engine::Source::from_bytes(script)) {
+            Ok(_) => {}
+            Err(e) if e.as_native().is_some_and(|e| e.is_runtime_limit()) => {
+                tracing::warn!(
+                    index,
+                    elapsed_ms = started.elapsed().as_millis(),
+                    "script exceeded the runtime limits and was skipped: {e}"
+                );
+            }
+            Err(e) => tracing::debug!(index, "uncaught exception in script: {e}"),
+        }
+    }
+    let data = read(
+        context
+            .global_object()
+            .get(js_str!("KENT"), &mut context)
+            .map_err(|e| anyhow!("failed to read KENT: {e}"))?
+            .to_json(&mut context)
+            .map_err(|e| anyhow!("failed to convert KENT to JSON: {e}"))?,
+    )?;
+    Ok(data)
+}
@@ -2,7 +2,8 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Data {
     pub(crate) assets_base_url: String,
     pub(crate) event_campuses: Vec<EventCampus>,
@@ -13,17 +14,21 @@ pub struct Data {
     pub(crate) events_base_url: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub enum EventCampus {
     Canterbury,
     #[serde(rename = "")]
+    #[default]
     Empty,
     Medway,
+    #[serde(untagged)]
+    Other(String),
 }
 
 pub type EventTags = HashMap<String, String>;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Event {
     pub(crate) all_day: bool,
     pub(crate) availability: String,
@@ -77,10 +82,13 @@ pub struct Event {
     pub(crate) tags: Vec<PrimaryCalendar>,
     pub(crate) tentative: bool,
     pub(crate) title: String,
+    /// Not listed on every page.
+    pub(crate) updated_at: Option<String>,
     pub(crate) url: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BannerImage {
     pub(crate) alt_text: String,
     pub(crate) attribution: Attribution,
@@ -96,7 +104,8 @@ pub struct BannerImage {
     pub(crate) updated_at: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Attribution {
     pub(crate) author: String,
     pub(crate) license: String,
@@ -107,29 +116,35 @@ pub struct Attribution {
 #[serde(rename_all = "snake_case")]
 pub enum Focus {
     Center,
+    #[serde(untagged)]
+    Other(String),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Sizes {
     pub(crate) full: Full,
     pub(crate) thumbnail: Thumbnail,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Full {
     pub(crate) height: String,
     pub(crate) url: String,
     pub(crate) width: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Thumbnail {
     pub(crate) height: i64,
     pub(crate) url: String,
     pub(crate) width: i64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PrimaryCalendar {
     pub(crate) id: i64,
     pub(crate) name: String,
@@ -142,27 +157,48 @@ pub type ContactPhone = String;
 pub enum Country {
     #[serde(rename = "United Kingdom")]
     UnitedKingdom,
+    #[serde(untagged)]
+    Other(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Month {
+    Jan,
+    Feb,
+    Mar,
+    Apr,
+    May,
+    Jun,
+    Jul,
     Aug,
-    Dec,
-    Nov,
-    Oct,
     Sep,
+    Oct,
+    Nov,
+    Dec,
+    #[serde(untagged)]
+    Other(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MonthFull {
+    January,
+    February,
+    March,
+    April,
+    May,
+    June,
+    July,
     August,
-    December,
-    November,
-    October,
     September,
+    October,
+    November,
+    December,
+    #[serde(untagged)]
+    Other(String),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Image {
     pub(crate) alt: String,
     pub(crate) height: i64,
@@ -174,36 +210,37 @@ pub type SeriesSlug = String;
 
 pub type SeriesTitle = String;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Breakpoints {
-    pub(crate) lg: i64,
-    pub(crate) md: i64,
-    pub(crate) sm: i64,
-    pub(crate) xl: i64,
-    pub(crate) xxl: i64,
-    pub(crate) xxxl: i64,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Img {
-    pub(crate) aspect_ratio: f64,
-    pub(crate) created_at: String,
-    pub(crate) created_by: i64,
-    pub(crate) deleted_at: Option<serde_json::Value>,
-    pub(crate) duration: Option<serde_json::Value>,
-    pub(crate) file: Vec<Option<serde_json::Value>>,
-    pub(crate) filename: String,
-    pub(crate) filesize: i64,
-    pub(crate) format: String,
-    pub(crate) hash: String,
-    pub(crate) height: i64,
-    pub(crate) id: i64,
-    pub(crate) mime_type: String,
-    #[serde(rename = "type")]
-    pub(crate) img_type: String,
-    pub(crate) updated_at: String,
-    pub(crate) updated_by: i64,
-    pub(crate) url: String,
-    pub(crate) variants: String,
-    pub(crate) width: i64,
-}
+/// Adds [`unknown`](Month::unknown) to enums with an `Other` variant for
+/// values added upstream since the schema was written.
+macro_rules! open_enum {
+    ($($t:ty),*) => {
+        $(
+            impl $t {
+                /// The value, if it isn't one of the known variants.
+                pub(crate) fn unknown(&self) -> Option<&str> {
+                    match self {
+                        Self::Other(value) if !value.is_empty() => Some(value),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+open_enum!(EventCampus, Focus, Country, Month, MonthFull);
+
+// Missing values deserialize as an empty `Other`.
+macro_rules! default_other {
+    ($($t:ty),*) => {
+        $(
+            impl Default for $t {
+                fn default() -> Self {
+                    Self::Other(String::new())
+                }
+            }
+        )*
+    };
+}
+
+default_other!(Focus, Country, Month, MonthFull);
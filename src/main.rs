@@ -1,39 +1,47 @@
-use std::{borrow::Cow, sync::LazyLock, time::Duration};
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    sync::{Arc, LazyLock},
+    time::{Duration, Instant},
+};
 
 use axum::{
     error_handling::HandleErrorLayer,
-    extract::{Request, State},
+    extract::{Path, Query, Request, State},
     http::{Method, StatusCode},
     response::{Html, IntoResponse, Response},
     routing::get,
-    Extension, Router,
+    Json, Router,
 };
+use chrono::{TimeZone, Utc};
+use chrono_tz::Europe::London;
+use futures_util::future::join_all;
 use http::{header, HeaderValue};
-use icalendar::Calendar;
-use moka::future::Cache;
+use moka::{future::Cache, Expiry};
 use tokio::{net::TcpListener, signal};
 use tower::ServiceBuilder;
 use tower_http::{timeout::TimeoutLayer, trace::TraceLayer};
 use tracing::info;
 
+use config::Config;
+use drift::DriftReports;
+use filter::Filter;
+use source::{Feed, FetchOptions, OutputOptions, Registration};
+
+mod archive;
 mod calendars;
+mod config;
+mod description;
+mod drift;
+mod filter;
+mod gazetteer;
+mod kent_data;
 mod kent_schema;
+mod merge;
+mod source;
 mod sums_pluto_schema;
-
-// #[tokio::main]
-// async fn main() -> Result<(), anyhow::Error> {
-//     // let calendar = kent_calendar("https://student.kent.ac.uk/events").await?;
-//     // calendar.print()?;
-//     let calendar = calendars::sums_calendar(
-//         "UutZYcRjdM5RzX2mnC8zPR",
-//         "Kent SU Calendar",
-//         "Hello Kent",
-//         |e| format!("https://hellokent.co.uk/events/id/{}", e.id),
-//     )
-//     .await?;
-//     calendar.print()?;
-//     Ok(())
-// }
+#[cfg(test)]
+mod tests;
 
 git_testament::git_testament!(TESTAMENT);
 
@@ -45,7 +53,7 @@ struct HtmlFiles;
 
 #[tokio::main()]
 async fn main() -> Result<(), anyhow::Error> {
-    let _guard = tracing_setup()?;
+    tracing_setup()?;
     run_server().await?;
     Ok(())
 }
@@ -62,7 +70,8 @@ fn tracing_setup() -> Result<(), anyhow::Error> {
 async fn run_server() -> Result<(), anyhow::Error> {
     info!("Starting server version {}", *VERSION);
 
-    let host = std::env::var("HOST").unwrap_or_else(|_| "0.0.0.0:3779".to_string());
+    let config = Config::load()?;
+    let host = config.host.clone();
     let mut listenfd = listenfd::ListenFd::from_env();
 
     let listener = if let Ok(Some(listener)) = listenfd.take_tcp_listener(0) {
@@ -74,33 +83,91 @@ async fn run_server() -> Result<(), anyhow::Error> {
         TcpListener::bind(host).await.unwrap()
     };
 
+    let app = router(config)?;
+
+    axum::serve(listener, app.into_make_service())
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
+
+    Ok(())
+}
+
+/// Build the service's routes from the configuration.
+fn router(mut config: Config) -> Result<Router, anyhow::Error> {
+    // Each router fetches and checks feeds for itself, even when built from
+    // clones of one configuration.
+    config.feed_cache = FeedCache::default();
+    config.drift_reports = DriftReports::default();
+    let sources = config.registrations()?;
+    let index = index_page(&sources);
+    let drift_reports = config.drift_reports.clone();
+
     let app = Router::new()
+        .route("/", get(|| async { Html(index) }))
         .route(
-            "/",
-            get(|| async {
-                let page = HtmlFiles::get("index.html").unwrap();
-                let body = page.data;
-                Html(body).into_response()
-            }),
+            "/diagnostics/drift",
+            get(|| async move { Json(drift_reports.all()) }),
         )
-        .route("/kent_public_calendar.ics", get(kent_public_calendar))
-        .route("/kent_student_calendar.ics", get(kent_student_calendar))
-        .route("/kent_union_calendar.ics", get(kent_union_calendar))
         .fallback(not_found_handler);
 
+    let app = sources.into_iter().fold(app, |app, registration| {
+        app.route(
+            &format!("/archive/:year{}", registration.path),
+            get(archive_handler).with_state(registration.clone()),
+        )
+        .route(
+            &registration.path.clone(),
+            get(calendar_handler).with_state(registration),
+        )
+    });
+    let calendars = config
+        .calendars
+        .iter()
+        .map(|calendar| calendar.registration(&config))
+        .collect::<Vec<_>>();
+    let app = app
+        .route(
+            "/combined.ics",
+            get(combined_handler).with_state((Arc::new(calendars), config.output())),
+        )
+        .route(
+            "/sums/:file",
+            get(sums_handler).with_state(Arc::new(config)),
+        );
+
     let load_service = ServiceBuilder::new()
         .layer(HandleErrorLayer::new(handle_error))
         .load_shed()
         .concurrency_limit(2 ^ 12)
         .layer(TimeoutLayer::new(Duration::from_secs(60)));
 
-    let app = app.layer(TraceLayer::new_for_http()).layer(load_service);
+    Ok(app.layer(TraceLayer::new_for_http()).layer(load_service))
+}
 
-    axum::serve(listener, app.into_make_service())
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+/// Render the index page, listing every registered calendar.
+fn index_page(sources: &[Registration]) -> String {
+    let page = HtmlFiles::get("index.html").unwrap();
+    let template = String::from_utf8_lossy(&page.data);
+    let calendars = sources
+        .iter()
+        .map(|registration| {
+            format!(
+                "        <li><a href=\"{}\">{}</a></li>",
+                escape_html(&registration.path),
+                escape_html(registration.name())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    template.replace("{{calendars}}", &calendars)
+}
 
-    Ok(())
+/// Text escaped for use in HTML.
+pub(crate) fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
 }
 
 #[tracing::instrument(skip(req))]
@@ -119,123 +186,296 @@ async fn not_found_handler(req: Request) -> Response {
     }
 }
 
-static CACHE: LazyLock<Cache<&str, String>> = LazyLock::new(|| {Cache::builder()
-    // Time to live (TTL): 60 minutes
-    .time_to_live(Duration::from_secs(60 * 60))
-    // Create the cache.
-    .build()
-  });
-
-async fn kent_public_calendar() -> Response {
-    let calendar = CACHE
-        .try_get_with::<_, anyhow::Error>("kent-pub-cal", async move {
-            println!("kent calendar retrieval");
-            Ok(format!(
-                "{}",
-                calendars::kent_calendar("https://www.kent.ac.uk/whats-on").await?
-            ))
-        })
-        .await;
-    match calendar {
-        Ok(cal) => {
-            tracing::info!("kent pub calendar retrieved");
-            (
-                StatusCode::OK,
-                [(
-                    header::CONTENT_TYPE,
-                    HeaderValue::from_static("text/calendar; charset=utf-8"),
-                )],
-                cal,
-            )
-                .into_response()
+/// A fetched feed, along with how long it should be cached for.
+#[derive(Clone)]
+pub(crate) struct CachedFeed {
+    feed: Arc<Feed>,
+    ttl: Duration,
+}
+
+/// Expires each cached feed after the time to live of its calendar.
+struct FeedExpiry;
+
+impl Expiry<CacheKey, CachedFeed> for FeedExpiry {
+    fn expire_after_create(
+        &self,
+        _key: &CacheKey,
+        value: &CachedFeed,
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        Some(value.ttl)
+    }
+}
+
+/// Feeds are cached per source, and per set of options passed upstream.
+type CacheKey = (String, FetchOptions);
+
+/// The fetched feeds of one router.
+#[derive(Clone)]
+pub(crate) struct FeedCache(Cache<CacheKey, CachedFeed>);
+
+impl Default for FeedCache {
+    fn default() -> Self {
+        Self(Cache::builder().expire_after(FeedExpiry).build())
+    }
+}
+
+impl std::fmt::Debug for FeedCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FeedCache")
+            .field("entries", &self.0.entry_count())
+            .finish()
+    }
+}
+
+/// Serves any union on the SUMS Pluto platform at `/sums/{site}.ics`.
+async fn sums_handler(
+    State(config): State<Arc<Config>>,
+    Path(file): Path<String>,
+    query: Query<Vec<(String, String)>>,
+) -> Response {
+    let registration = file
+        .strip_suffix(".ics")
+        .and_then(|site| config.sums_registration(site));
+    match registration {
+        Some(registration) => calendar_handler(State(registration), query).await,
+        None => not_found_handler(Request::default()).await,
+    }
+}
+
+#[tracing::instrument(skip_all, fields(source = registration.source.id()))]
+async fn calendar_handler(
+    State(registration): State<Registration>,
+    Query(params): Query<Vec<(String, String)>>,
+) -> Response {
+    let filter = match route_filter(&registration, params) {
+        Ok(filter) => filter,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    match cached_feed(&registration, &filter.options).await {
+        Ok(feed) => {
+            tracing::info!("calendar retrieved");
+            let feed = with_history(&registration, &filter, feed).await;
+            calendar_response(&filter.apply(&feed), &registration.output)
         }
         Err(e) => {
-            tracing::error!("kent pub calendar retrieval failed: {e}");
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Internal Server Error",
-            )
-                .into_response()
+            tracing::error!("calendar retrieval failed: {e}");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error").into_response()
         }
     }
 }
 
+/// Serves every archived event of a calendar that starts in a year, at
+/// `/archive/{year}/{path}`.
+///
+/// The calendar is fetched first so that the archive is up to date, but the
+/// archive is served even if that fails.
+#[tracing::instrument(skip_all, fields(source = registration.source.id()))]
+async fn archive_handler(
+    State(registration): State<Registration>,
+    Path(year): Path<i32>,
+    Query(params): Query<Vec<(String, String)>>,
+) -> Response {
+    let filter = match route_filter(&registration, params) {
+        Ok(filter) => filter,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    let start_of = |year| London.with_ymd_and_hms(year, 1, 1, 0, 0, 0).earliest();
+    let (Some(archive), Some(from), Some(until)) =
+        (&registration.archive, start_of(year), start_of(year + 1))
+    else {
+        return not_found_handler(Request::default()).await;
+    };
 
-async fn kent_student_calendar() -> Response {
-    let calendar = CACHE
-        .try_get_with::<_, anyhow::Error>("kent-student-cal", async move {
-            println!("kent calendar retrieval");
-            Ok(format!(
-                "{}",
-                calendars::kent_calendar("https://student.kent.ac.uk/events").await?
-            ))
-        })
-        .await;
-    match calendar {
-        Ok(cal) => {
-            tracing::info!("kent pub calendar retrieved");
-            (
-                StatusCode::OK,
-                [(
-                    header::CONTENT_TYPE,
-                    HeaderValue::from_static("text/calendar; charset=utf-8"),
-                )],
-                cal,
-            )
-                .into_response()
+    let title = match cached_feed(&registration, &filter.options).await {
+        Ok(feed) => feed.title.clone(),
+        Err(e) => {
+            tracing::warn!("calendar retrieval failed, serving archive only: {e}");
+            registration.name().to_owned()
         }
+    };
+    let events = archive
+        .events(
+            registration.source.id(),
+            &filter.options,
+            from.to_utc(),
+            until.to_utc(),
+        )
+        .await;
+    match events {
+        Ok(events) => calendar_response(
+            &filter.apply(&Feed {
+                title: format!("{title} ({year})"),
+                description: format!("Events from {year}"),
+                events,
+            }),
+            &registration.output,
+        ),
         Err(e) => {
-            tracing::error!("kent pub calendar retrieval failed: {e}");
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Internal Server Error",
-            )
-                .into_response()
+            tracing::error!("archive retrieval failed: {e}");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error").into_response()
         }
     }
 }
 
+/// The filters for a request to a route, after those of its preset.
+fn route_filter(
+    registration: &Registration,
+    params: Vec<(String, String)>,
+) -> Result<Filter, anyhow::Error> {
+    let params = registration
+        .preset
+        .iter()
+        .flat_map(|preset| preset.query.iter().cloned())
+        .chain(params)
+        .collect::<Vec<_>>();
+    Filter::from_query(&params)
+}
 
+/// Serves several calendars merged into one, at
+/// `/combined.ics?sources={id},{id}`.
+///
+/// Without `sources`, every configured calendar is included.
+async fn combined_handler(
+    State((calendars, output)): State<(Arc<Vec<Registration>>, OutputOptions)>,
+    Query(params): Query<Vec<(String, String)>>,
+) -> Response {
+    let filter = match Filter::from_query(&params) {
+        Ok(filter) => filter,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    let ids = params
+        .iter()
+        .filter(|(key, _)| key == "sources")
+        .flat_map(|(_, value)| value.split(','))
+        .collect::<Vec<_>>();
+    let selected = if ids.is_empty() {
+        calendars.iter().collect::<Vec<_>>()
+    } else {
+        let mut selected = Vec::new();
+        for id in ids {
+            match calendars.iter().find(|calendar| calendar.source.id() == id) {
+                Some(calendar) => selected.push(calendar),
+                None => {
+                    return (StatusCode::BAD_REQUEST, format!("unknown source {id}"))
+                        .into_response()
+                }
+            }
+        }
+        selected
+    };
 
-async fn kent_union_calendar() -> Response {
-    let calendar = CACHE
-        .try_get_with::<_, anyhow::Error>("kent-union-cal", async move {
-            println!("kent calendar retrieval");
-            Ok(format!(
-                "{}",
-                calendars::sums_calendar(
-                            "UutZYcRjdM5RzX2mnC8zPR",
-                            "Kent SU Calendar",
-                            "Hello Kent",
-                            |e| format!("https://hellokent.co.uk/events/id/{}", e.id),
-                        ).await?)
-            )
+    let feeds = join_all(selected.iter().map(|registration| async {
+        let feed = cached_feed(registration, &filter.options).await?;
+        Ok::<_, Arc<anyhow::Error>>(with_history(registration, &filter, feed).await)
+    }))
+    .await
+    .into_iter()
+    .collect::<Result<Vec<_>, _>>();
+    let feeds = match feeds {
+        Ok(feeds) => feeds,
+        Err(e) => {
+            tracing::error!("calendar retrieval failed: {e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error").into_response();
+        }
+    };
+
+    let names = selected
+        .iter()
+        .map(|registration| registration.source.name())
+        .collect::<Vec<_>>();
+    let feed = Feed {
+        title: "Combined Calendar".to_owned(),
+        description: names.join(", "),
+        events: merge::merge(names.iter().copied().zip(feeds.iter().map(AsRef::as_ref))),
+    };
+    calendar_response(&filter.apply(&feed), &output)
+}
+
+/// Fetch a source's feed, or reuse it from the cache.
+async fn cached_feed(
+    registration: &Registration,
+    options: &FetchOptions,
+) -> Result<Arc<Feed>, Arc<anyhow::Error>> {
+    let source = registration.source.clone();
+    let archive = registration.archive.clone();
+    let ttl = registration.ttl;
+    let key = (source.id().to_owned(), options.clone());
+    let options = options.clone();
+    registration
+        .cache
+        .0
+        .try_get_with(key, async move {
+            tracing::info!(source = source.id(), "calendar retrieval");
+            let mut feed = source.fetch(&options).await?;
+            for event in &mut feed.events {
+                event.cancelled |= event.title_says_cancelled();
+            }
+            if let Some(archive) = archive {
+                if let Err(e) = archive
+                    .update(source.id(), &options, &mut feed.events)
+                    .await
+                {
+                    tracing::error!(source = source.id(), "archiving events failed: {e}");
+                }
+            }
+            Ok::<_, anyhow::Error>(CachedFeed {
+                feed: Arc::new(feed),
+                ttl,
+            })
         })
+        .await
+        .map(|cached| cached.feed)
+}
+
+/// Add the archived events from the `history` requested in `filter` that
+/// upstream no longer lists.
+async fn with_history(registration: &Registration, filter: &Filter, feed: Arc<Feed>) -> Arc<Feed> {
+    let (Some(archive), Some(history)) = (&registration.archive, filter.history) else {
+        return feed;
+    };
+    let now = Utc::now();
+    let archived = archive
+        .events(
+            registration.source.id(),
+            &filter.options,
+            now - history,
+            now,
+        )
         .await;
-    match calendar {
-        Ok(cal) => {
-            tracing::info!("kent pub calendar retrieved");
-            (
-                StatusCode::OK,
-                [(
-                    header::CONTENT_TYPE,
-                    HeaderValue::from_static("text/calendar; charset=utf-8"),
-                )],
-                cal,
-            )
-                .into_response()
-        }
+    let archived = match archived {
+        Ok(archived) => archived,
         Err(e) => {
-            tracing::error!("kent pub calendar retrieval failed: {e}");
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Internal Server Error",
-            )
-                .into_response()
+            tracing::error!("archive retrieval failed: {e}");
+            return feed;
         }
-    }
+    };
+
+    let listed = feed
+        .events
+        .iter()
+        .map(|event| (&event.origin, &event.uid))
+        .collect::<HashSet<_>>();
+    let past = archived
+        .into_iter()
+        .filter(|event| !listed.contains(&(&event.origin, &event.uid)))
+        .collect::<Vec<_>>();
+    let mut feed = Feed::clone(&feed);
+    feed.events.splice(0..0, past);
+    Arc::new(feed)
 }
 
+fn calendar_response(feed: &Feed, output: &OutputOptions) -> Response {
+    (
+        StatusCode::OK,
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/calendar; charset=utf-8"),
+        )],
+        feed.to_ics(output),
+    )
+        .into_response()
+}
 
 async fn handle_error(error: tower::BoxError) -> impl IntoResponse {
     if error.is::<tower::timeout::error::Elapsed>() {
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::source::{Event, Feed};
+
+/// Merge the feeds of several sources into one, collapsing duplicate events.
+///
+/// Events are duplicates if they have the same upstream id, or if different
+/// sources list them with the same title and start time. A source can list
+/// two events with the same title and start, such as socials run by two
+/// societies, so those are kept apart. The first copy of each event is kept,
+/// with a note of every source that listed it.
+pub(crate) fn merge<'a>(feeds: impl IntoIterator<Item = (&'a str, &'a Feed)>) -> Vec<Event> {
+    let mut events: Vec<(Event, Vec<&str>)> = Vec::new();
+    let mut by_id = HashMap::new();
+    let mut by_title = HashMap::new();
+
+    for (name, feed) in feeds {
+        for event in &feed.events {
+            let id_key = (event.origin.clone(), event.uid.clone());
+            let title_key = event
+                .starts_at()
+                .map(|start| title_key(&event.title, start));
+            let existing = by_id.get(&id_key).copied().or_else(|| {
+                let indices: &Vec<usize> = by_title.get(title_key.as_ref()?)?;
+                indices
+                    .iter()
+                    .copied()
+                    .find(|&index| !events[index].1.contains(&name))
+            });
+
+            let index = match existing {
+                Some(index) => index,
+                None => {
+                    events.push((event.clone(), Vec::new()));
+                    events.len() - 1
+                }
+            };
+            by_id.insert(id_key, index);
+            if let Some(title_key) = title_key {
+                let indices = by_title.entry(title_key).or_insert_with(Vec::new);
+                if !indices.contains(&index) {
+                    indices.push(index);
+                }
+            }
+
+            let sources = &mut events[index].1;
+            if !sources.contains(&name) {
+                sources.push(name);
+            }
+        }
+    }
+
+    events
+        .into_iter()
+        .map(|(mut event, sources)| {
+            event.add_note(&format!("Listed in: {}", sources.join(", ")));
+            event
+        })
+        .collect()
+}
+
+/// A title and start time, ignoring differences in case, punctuation and
+/// spacing in the title.
+fn title_key(title: &str, start: DateTime<Utc>) -> (String, DateTime<Utc>) {
+    let title = title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ");
+    (title, start)
+}
@@ -0,0 +1,784 @@
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use chrono::{DateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::{Europe::London, Tz};
+use icalendar::{
+    Calendar, CalendarDateTime, Component, DatePerhapsTime, EventLike, EventStatus, Property,
+};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::{archive::Archive, escape_html, FeedCache};
+
+/// An upstream that can be turned into a calendar.
+///
+/// Each scraper implements this, and the server exposes every registered
+/// source through the same handler.
+#[async_trait::async_trait]
+pub(crate) trait CalendarSource: Send + Sync {
+    /// Stable identifier, used as the cache key.
+    fn id(&self) -> &str;
+
+    /// Human readable name, shown on the index page.
+    fn name(&self) -> &str;
+
+    /// Retrieve the current events from upstream.
+    ///
+    /// Sources apply whichever `options` their upstream supports, and ignore
+    /// the rest.
+    async fn fetch(&self, options: &FetchOptions) -> Result<Feed, anyhow::Error>;
+}
+
+/// Options that change what is requested from upstream, rather than being
+/// applied to the fetched events.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize)]
+pub(crate) struct FetchOptions {
+    pub(crate) premium: Premium,
+}
+
+/// Which SUMS Pluto events to list.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Premium {
+    /// Only premium events, which are the ones promoted on the union's site.
+    #[default]
+    Only,
+    All,
+}
+
+/// A source mounted at a route.
+#[derive(Clone)]
+pub(crate) struct Registration {
+    pub(crate) path: String,
+    /// How long a fetched feed is cached for.
+    pub(crate) ttl: Duration,
+    pub(crate) source: Arc<dyn CalendarSource>,
+    /// Filters applied before those in the request.
+    pub(crate) preset: Option<Preset>,
+    /// Where every fetched event is recorded, if anywhere.
+    pub(crate) archive: Option<Archive>,
+    /// Where fetched feeds are kept, shared by every route of a router.
+    pub(crate) cache: FeedCache,
+    pub(crate) output: OutputOptions,
+}
+
+/// Choices about how events are written to a calendar.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct OutputOptions {
+    pub(crate) uid_format: UidFormat,
+    /// Also attach each event's full size image, for clients that don't show
+    /// IMAGE properties.
+    pub(crate) attach_images: bool,
+    pub(crate) category_names: Arc<CategoryNames>,
+}
+
+/// Names to write categories under, so that the same category is called the
+/// same thing whichever source lists it.
+///
+/// Keys are the name or slug of a category, tag, group or event type, in any
+/// case. Mapping one to an empty name leaves it out.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct CategoryNames(BTreeMap<String, String>);
+
+impl CategoryNames {
+    /// The name to write a term under, if any.
+    fn name<'a>(&'a self, term: &'a Term) -> Option<&'a str> {
+        let renamed = self.0.iter().find(|(key, _)| {
+            key.eq_ignore_ascii_case(&term.name) || key.eq_ignore_ascii_case(&term.slug)
+        });
+        let name = match renamed {
+            Some((_, name)) => name,
+            None => &term.name,
+        };
+        Some(name.trim()).filter(|name| !name.is_empty())
+    }
+}
+
+/// How the UIDs of events are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum UidFormat {
+    /// `{id}@{origin}`, such as `12345@kent.ac.uk`, which is unique across
+    /// every source.
+    #[default]
+    Namespaced,
+    /// The upstream's id alone, as UIDs used to be written, so that existing
+    /// subscribers don't see every event twice.
+    Legacy,
+}
+
+/// A route serving a filtered view of another calendar.
+#[derive(Debug, Clone)]
+pub(crate) struct Preset {
+    pub(crate) name: String,
+    pub(crate) query: Vec<(String, String)>,
+}
+
+impl Registration {
+    /// The name shown on the index page.
+    pub(crate) fn name(&self) -> &str {
+        match &self.preset {
+            Some(preset) => &preset.name,
+            None => self.source.name(),
+        }
+    }
+}
+
+/// The normalized result of fetching a source.
+#[derive(Debug, Clone)]
+pub(crate) struct Feed {
+    pub(crate) title: String,
+    pub(crate) description: String,
+    pub(crate) events: Vec<Event>,
+}
+
+/// A single event, independent of which upstream it came from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Event {
+    /// The upstream's id for the event.
+    pub(crate) uid: String,
+    /// The upstream the event came from, within which `uid` is unique.
+    pub(crate) origin: String,
+    pub(crate) title: String,
+    /// Plain text.
+    pub(crate) description: String,
+    /// The description as sanitized HTML, if upstream gave one, for clients
+    /// that show rich descriptions.
+    #[serde(default)]
+    pub(crate) description_html: Option<String>,
+    #[serde(with = "stored_time")]
+    pub(crate) start: DatePerhapsTime,
+    #[serde(with = "stored_time")]
+    pub(crate) end: DatePerhapsTime,
+    /// Where the event physically takes place, if anywhere.
+    pub(crate) location: Option<String>,
+    /// The place `location` refers to, if it's known.
+    #[serde(default)]
+    pub(crate) place: Option<Place>,
+    pub(crate) url: Option<String>,
+    pub(crate) online: bool,
+    /// Link to join an online event, if known.
+    pub(crate) join_url: Option<String>,
+    pub(crate) tentative: bool,
+    #[serde(default)]
+    pub(crate) cancelled: bool,
+    pub(crate) campus: Option<Campus>,
+    pub(crate) categories: Vec<Term>,
+    pub(crate) tags: Vec<Term>,
+    pub(crate) group: Option<Term>,
+    pub(crate) venue: Option<Term>,
+    pub(crate) event_type: Option<Term>,
+    /// Who is running the event, and how to reach them.
+    #[serde(default)]
+    pub(crate) organizer: Option<Contact>,
+    /// How to get in, if upstream says.
+    #[serde(default)]
+    pub(crate) tickets: Option<Tickets>,
+    #[serde(default)]
+    pub(crate) images: Vec<Image>,
+    /// When upstream last changed the event, if it says.
+    pub(crate) updated: Option<DateTime<Utc>>,
+    /// The changes to the event seen so far, if they are being tracked.
+    pub(crate) revision: Option<Revision>,
+}
+
+/// How many times an event has changed, and when.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Revision {
+    /// Starts at 0, and goes up by one for each change.
+    pub(crate) sequence: u32,
+    /// When the event was first seen.
+    pub(crate) created: DateTime<Utc>,
+    /// When the event last changed.
+    pub(crate) modified: DateTime<Utc>,
+}
+
+/// The price of an event, and how to book it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Tickets {
+    /// The price, as upstream writes it, such as `Free` or `£5`.
+    pub(crate) price: Option<String>,
+    /// Who can attend, such as `Students`.
+    pub(crate) open_to: Option<String>,
+    /// Anything else upstream says about availability.
+    pub(crate) notes: Option<String>,
+    /// Where to book.
+    pub(crate) url: Option<String>,
+    pub(crate) availability: Option<Availability>,
+    /// Whether places have to be booked in advance.
+    pub(crate) bookable: bool,
+}
+
+/// How many tickets are left, when it's known to be few or none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum Availability {
+    Limited,
+    SoldOut,
+}
+
+/// A person, team or society to ask about an event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Contact {
+    pub(crate) name: String,
+    /// Describes who they are, such as the kind of society.
+    pub(crate) note: Option<String>,
+    pub(crate) email: Option<String>,
+    pub(crate) phone: Option<String>,
+    /// A page about them.
+    pub(crate) url: Option<String>,
+}
+
+/// A building or venue, from the gazetteer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Place {
+    pub(crate) name: String,
+    pub(crate) address: Option<String>,
+    pub(crate) geo: Option<Geo>,
+}
+
+/// A position in WGS 84 degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Geo {
+    pub(crate) latitude: f64,
+    pub(crate) longitude: f64,
+}
+
+/// A picture of an event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Image {
+    pub(crate) url: String,
+    pub(crate) display: ImageDisplay,
+    /// Describes the image, for people who can't see it.
+    pub(crate) alt: Option<String>,
+}
+
+/// How an image is meant to be shown, from RFC 7986.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum ImageDisplay {
+    Fullsize,
+    Graphic,
+    Thumbnail,
+}
+
+/// Whether an event is attended online or in person.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Mode {
+    Online,
+    InPerson,
+}
+
+/// A University of Kent campus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum Campus {
+    Canterbury,
+    Medway,
+}
+
+/// A category, group, venue or similar that an event belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Term {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) slug: String,
+}
+
+/// Europe/London, which every date-time is written in.
+///
+/// These are the UK's daylight saving rules since 1996: British Summer Time
+/// runs from 01:00 UTC on the last Sunday in March to 01:00 UTC on the last
+/// Sunday in October.
+const LONDON_VTIMEZONE: &str = "BEGIN:VTIMEZONE\r
+TZID:Europe/London\r
+X-LIC-LOCATION:Europe/London\r
+BEGIN:DAYLIGHT\r
+TZOFFSETFROM:+0000\r
+TZOFFSETTO:+0100\r
+TZNAME:BST\r
+DTSTART:19700329T010000\r
+RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU\r
+END:DAYLIGHT\r
+BEGIN:STANDARD\r
+TZOFFSETFROM:+0100\r
+TZOFFSETTO:+0000\r
+TZNAME:GMT\r
+DTSTART:19701025T020000\r
+RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU\r
+END:STANDARD\r
+END:VTIMEZONE\r
+";
+
+impl Feed {
+    pub(crate) fn to_calendar(&self, output: &OutputOptions) -> Calendar {
+        let mut calendar = Calendar::new();
+        calendar
+            .name(&self.title)
+            .description(&self.description)
+            .timezone("Europe/London");
+
+        for event in &self.events {
+            calendar.push(event.to_ical(output));
+        }
+
+        calendar.done()
+    }
+
+    /// The calendar as an iCalendar file, with the definition of the
+    /// Europe/London time zone its events refer to.
+    pub(crate) fn to_ics(&self, output: &OutputOptions) -> String {
+        let mut ics = self.to_calendar(output).to_string();
+        // Added by hand, since `icalendar` gives every component it writes
+        // a DTSTAMP and UID, which a VTIMEZONE mustn't have.
+        let index = ics
+            .find("\r\nBEGIN:VEVENT\r\n")
+            .or_else(|| ics.find("\r\nEND:VCALENDAR"))
+            .map_or(ics.len(), |index| index + "\r\n".len());
+        ics.insert_str(index, LONDON_VTIMEZONE);
+        ics
+    }
+}
+
+impl Term {
+    /// Create a term, deriving its slug from the name.
+    pub(crate) fn new(id: impl ToString, name: &str) -> Self {
+        let slug = name
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|part| !part.is_empty())
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>()
+            .join("-");
+        Self {
+            id: id.to_string(),
+            name: name.to_owned(),
+            slug,
+        }
+    }
+
+    /// Whether `value` is this term's id, name or slug.
+    pub(crate) fn matches(&self, value: &str) -> bool {
+        self.id == value
+            || self.slug.eq_ignore_ascii_case(value)
+            || self.name.eq_ignore_ascii_case(value)
+    }
+}
+
+impl Event {
+    /// When the event starts, treating floating times as UK local time.
+    pub(crate) fn starts_at(&self) -> Option<DateTime<Utc>> {
+        let local = match &self.start {
+            DatePerhapsTime::DateTime(CalendarDateTime::Utc(start)) => return Some(*start),
+            DatePerhapsTime::DateTime(CalendarDateTime::Floating(start)) => *start,
+            DatePerhapsTime::DateTime(CalendarDateTime::WithTimezone { date_time, tzid }) => {
+                let tz: Tz = tzid.parse().ok()?;
+                return tz
+                    .from_local_datetime(date_time)
+                    .earliest()
+                    .map(|start| start.to_utc());
+            }
+            DatePerhapsTime::Date(date) => date.and_time(NaiveTime::MIN),
+        };
+        London
+            .from_local_datetime(&local)
+            .earliest()
+            .map(|start| start.to_utc())
+    }
+
+    /// Whether the title says the event is cancelled or postponed, which
+    /// organisers often do instead of removing it.
+    pub(crate) fn title_says_cancelled(&self) -> bool {
+        self.title.split(|c: char| !c.is_alphabetic()).any(|word| {
+            ["cancelled", "canceled", "postponed"]
+                .iter()
+                .any(|marker| word.eq_ignore_ascii_case(marker))
+        })
+    }
+
+    /// The names of the event's categories, tags, type and group, renamed by
+    /// `names` and without duplicates.
+    fn category_names<'a>(&'a self, names: &'a CategoryNames) -> Vec<&'a str> {
+        let mut categories: Vec<&str> = Vec::new();
+        let terms = self
+            .categories
+            .iter()
+            .chain(&self.tags)
+            .chain(&self.event_type)
+            .chain(&self.group);
+        for name in terms.filter_map(|term| names.name(term)) {
+            if !categories
+                .iter()
+                .any(|category| category.eq_ignore_ascii_case(name))
+            {
+                categories.push(name);
+            }
+        }
+        categories
+    }
+
+    /// Add a paragraph to the end of the description. Lines in the paragraph
+    /// are kept apart in the HTML description too.
+    pub(crate) fn add_note(&mut self, note: &str) {
+        if self.description.is_empty() {
+            self.description = note.to_owned();
+        } else {
+            self.description = format!("{}\n\n{note}", self.description);
+        }
+        if let Some(html) = &mut self.description_html {
+            let note = escape_html(note).replace('\n', "<br>");
+            html.push_str(&format!("<p>{note}</p>"));
+        }
+    }
+
+    /// The event's UID in a calendar.
+    pub(crate) fn ical_uid(&self, format: UidFormat) -> String {
+        match format {
+            UidFormat::Namespaced => format!("{}@{}", self.uid, self.origin),
+            UidFormat::Legacy => self.uid.clone(),
+        }
+    }
+
+    fn to_ical(&self, output: &OutputOptions) -> icalendar::Event {
+        let mut cal_event = icalendar::Event::new();
+        cal_event
+            .summary(&self.title)
+            .description(&self.description)
+            .starts(in_london(&self.start))
+            .ends(in_london(&self.end))
+            .uid(&self.ical_uid(output.uid_format));
+
+        if let Some(html) = &self.description_html {
+            // Outlook's property, which other clients have adopted. Unlike
+            // DESCRIPTION, `icalendar` doesn't know it's text, so doesn't
+            // escape it.
+            cal_event.append_property(
+                Property::new("X-ALT-DESC", escape_text(html))
+                    .add_parameter("FMTTYPE", "text/html")
+                    .done(),
+            );
+        }
+        if let Some(location) = &self.location {
+            // A link to the place on a map. Unlike the structured location
+            // below, it doesn't need a position, so venues known only by
+            // their address have one too.
+            let mut property = Property::new("LOCATION", location);
+            if let Some(map) = self.place.as_ref().and_then(Place::map_url) {
+                property.add_parameter("ALTREP", &map);
+            }
+            cal_event.append_property(property.done());
+        }
+        if let Some(Place {
+            name,
+            address,
+            geo: Some(geo),
+        }) = &self.place
+        {
+            let position = format!("{};{}", geo.latitude, geo.longitude);
+            cal_event.add_property("GEO", position);
+            // Apple's calendars show this as a map pin, with directions to
+            // the address. Its value has to be a position, so places without
+            // one, such as SUMS venues missing from the gazetteer, only have
+            // the map link on LOCATION.
+            let mut property = Property::new(
+                "X-APPLE-STRUCTURED-LOCATION",
+                format!("geo:{},{}", geo.latitude, geo.longitude),
+            );
+            property
+                .add_parameter("VALUE", "URI")
+                .add_parameter("X-APPLE-RADIUS", "70")
+                .add_parameter("X-TITLE", &parameter_text(name));
+            if let Some(address) = address {
+                property.add_parameter("X-ADDRESS", &parameter_text(address));
+            }
+            cal_event.append_property(property.done());
+        }
+        if let Some(url) = &self.url {
+            cal_event.url(url);
+        }
+        if self.online {
+            // RFC 7986, so clients can offer a button to join the event.
+            if let Some(url) = self.join_url.as_ref().or(self.url.as_ref()) {
+                cal_event.append_multi_property(
+                    Property::new("CONFERENCE", url)
+                        .add_parameter("VALUE", "URI")
+                        .add_parameter("FEATURE", "VIDEO")
+                        .add_parameter("LABEL", "Join online")
+                        .done(),
+                );
+            }
+        }
+        // One property for each, since `icalendar` escapes the commas that
+        // would separate them in a single property.
+        for category in self.category_names(&output.category_names) {
+            cal_event.append_multi_property(Property::new("CATEGORIES", category));
+        }
+        if let Some(organizer) = &self.organizer {
+            // ORGANIZER needs an address, which is an email address if there
+            // is one, and otherwise their page.
+            let address = organizer
+                .email
+                .as_ref()
+                .map(|email| format!("mailto:{email}"))
+                .or_else(|| organizer.url.clone());
+            if let Some(address) = address {
+                let mut property = Property::new("ORGANIZER", address);
+                if !organizer.name.is_empty() {
+                    property.add_parameter("CN", &parameter_text(&organizer.name));
+                }
+                cal_event.append_property(property.done());
+            }
+            let mut property = Property::new("CONTACT", organizer.text());
+            if let Some(url) = &organizer.url {
+                property.add_parameter("ALTREP", url);
+            }
+            cal_event.append_property(property.done());
+        }
+        for image in &self.images {
+            let mut property = Property::new("IMAGE", &image.url);
+            property
+                .add_parameter("VALUE", "URI")
+                .add_parameter("DISPLAY", image.display.as_str());
+            if let Some(format) = image.format() {
+                property.add_parameter("FMTTYPE", format);
+            }
+            // RFC 7986 has no parameter for alt text, so this uses an
+            // extension parameter.
+            if let Some(alt) = &image.alt {
+                property.add_parameter("X-ALT-TEXT", &parameter_text(alt));
+            }
+            cal_event.append_multi_property(property.done());
+        }
+        let attachment = self
+            .images
+            .iter()
+            .find(|image| output.attach_images && image.display == ImageDisplay::Fullsize);
+        if let Some(image) = attachment {
+            let mut property = Property::new("ATTACH", &image.url);
+            property.add_parameter("VALUE", "URI");
+            if let Some(format) = image.format() {
+                property.add_parameter("FMTTYPE", format);
+            }
+            cal_event.append_multi_property(property.done());
+        }
+        if self.cancelled {
+            cal_event.status(EventStatus::Cancelled);
+        } else if self.tentative {
+            cal_event.status(EventStatus::Tentative);
+        }
+        // Clients compare these to tell whether their copy is out of date.
+        // Without them, DTSTAMP is the time the calendar was generated.
+        if let Some(revision) = self.revision {
+            cal_event
+                .sequence(revision.sequence)
+                .add_property("CREATED", format_utc(revision.created))
+                .add_property("LAST-MODIFIED", format_utc(revision.modified))
+                .timestamp(revision.modified);
+        } else if let Some(updated) = self.updated {
+            cal_event
+                .add_property("LAST-MODIFIED", format_utc(updated))
+                .timestamp(updated);
+        }
+
+        cal_event.done()
+    }
+}
+
+impl Tickets {
+    /// The tickets section of an event's description.
+    pub(crate) fn text(&self) -> String {
+        let mut lines = vec![match self.availability {
+            Some(Availability::Limited) => "Tickets (limited availability)".to_owned(),
+            Some(Availability::SoldOut) => "Tickets (sold out)".to_owned(),
+            None => "Tickets".to_owned(),
+        }];
+        if let Some(price) = &self.price {
+            lines.push(format!("Price: {price}"));
+        }
+        if let Some(open_to) = &self.open_to {
+            lines.push(format!("Open to: {open_to}"));
+        }
+        lines.extend(self.notes.clone());
+        match &self.url {
+            Some(url) => lines.push(format!("Book at: {url}")),
+            None if self.bookable => lines.push("Booking required.".to_owned()),
+            None => {}
+        }
+        lines.join("\n")
+    }
+}
+
+impl Place {
+    /// The place on OpenStreetMap, at its position if that's known, or else
+    /// found by searching for its address.
+    pub(crate) fn map_url(&self) -> Option<String> {
+        if let Some(Geo {
+            latitude,
+            longitude,
+        }) = self.geo
+        {
+            return Some(format!(
+                "https://www.openstreetmap.org/?mlat={latitude}&mlon={longitude}#map=18/{latitude}/{longitude}"
+            ));
+        }
+        let address = self.address.as_deref()?;
+        let search = format!("{}, {address}", self.name);
+        Url::parse_with_params(
+            "https://www.openstreetmap.org/search",
+            [("query", search.as_str())],
+        )
+        .ok()
+        .map(String::from)
+    }
+}
+
+impl Contact {
+    /// Their name and details on one line, such as
+    /// `Chess Society (Societies), https://example.com/chess`.
+    fn text(&self) -> String {
+        let mut parts = Vec::new();
+        match &self.note {
+            Some(note) if !self.name.is_empty() => parts.push(format!("{} ({note})", self.name)),
+            Some(note) => parts.push(note.clone()),
+            None if !self.name.is_empty() => parts.push(self.name.clone()),
+            None => {}
+        }
+        parts.extend(
+            self.email
+                .iter()
+                .chain(&self.phone)
+                .chain(&self.url)
+                .cloned(),
+        );
+        parts.join(", ")
+    }
+}
+
+impl Image {
+    /// The media type of the image, guessed from its URL.
+    fn format(&self) -> Option<&'static str> {
+        let path = self.url.split(['?', '#']).next()?;
+        let (_, extension) = path.rsplit_once('.')?;
+        match extension.to_ascii_lowercase().as_str() {
+            "jpg" | "jpeg" => Some("image/jpeg"),
+            "png" => Some("image/png"),
+            "gif" => Some("image/gif"),
+            "webp" => Some("image/webp"),
+            "svg" => Some("image/svg+xml"),
+            _ => None,
+        }
+    }
+}
+
+impl ImageDisplay {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Fullsize => "FULLSIZE",
+            Self::Graphic => "GRAPHIC",
+            Self::Thumbnail => "THUMBNAIL",
+        }
+    }
+}
+
+/// Text made safe to use as a property parameter value, which can't contain
+/// double quotes or control characters, and must be quoted if it contains a
+/// comma.
+///
+/// `icalendar` only quotes values that contain a colon or semicolon.
+fn parameter_text(text: &str) -> String {
+    let text = text
+        .chars()
+        .filter(|c| *c != '"' && !c.is_control())
+        .collect::<String>();
+    if text.contains(',') && !text.contains([':', ';']) {
+        format!("\"{text}\"")
+    } else {
+        text
+    }
+}
+
+/// Text escaped for use as a property value.
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+        .replace('\n', "\\n")
+}
+
+/// A date-time in Europe/London local time, treating floating times as
+/// already being in it. Dates are left as they are.
+fn in_london(time: &DatePerhapsTime) -> DatePerhapsTime {
+    let local = match time {
+        DatePerhapsTime::Date(_) => return time.clone(),
+        DatePerhapsTime::DateTime(CalendarDateTime::Floating(local)) => *local,
+        DatePerhapsTime::DateTime(CalendarDateTime::Utc(time)) => {
+            time.with_timezone(&London).naive_local()
+        }
+        DatePerhapsTime::DateTime(CalendarDateTime::WithTimezone { date_time, tzid }) => match tzid
+            .parse::<Tz>()
+            .ok()
+            .and_then(|tz| tz.from_local_datetime(date_time).earliest())
+        {
+            Some(time) => time.with_timezone(&London).naive_local(),
+            None => return time.clone(),
+        },
+    };
+    CalendarDateTime::WithTimezone {
+        date_time: local,
+        tzid: "Europe/London".to_owned(),
+    }
+    .into()
+}
+
+/// Format a time as an iCalendar UTC date-time.
+fn format_utc(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// (De)serializes the start and end of archived events.
+mod stored_time {
+    use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+    use icalendar::{CalendarDateTime, DatePerhapsTime};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    enum StoredTime {
+        Date(NaiveDate),
+        Floating(NaiveDateTime),
+        Utc(DateTime<Utc>),
+        WithTimezone {
+            date_time: NaiveDateTime,
+            tzid: String,
+        },
+    }
+
+    pub(super) fn serialize<S: Serializer>(
+        time: &DatePerhapsTime,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match time.clone() {
+            DatePerhapsTime::Date(date) => StoredTime::Date(date),
+            DatePerhapsTime::DateTime(CalendarDateTime::Floating(date_time)) => {
+                StoredTime::Floating(date_time)
+            }
+            DatePerhapsTime::DateTime(CalendarDateTime::Utc(date_time)) => {
+                StoredTime::Utc(date_time)
+            }
+            DatePerhapsTime::DateTime(CalendarDateTime::WithTimezone { date_time, tzid }) => {
+                StoredTime::WithTimezone { date_time, tzid }
+            }
+        }
+        .serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DatePerhapsTime, D::Error> {
+        Ok(match StoredTime::deserialize(deserializer)? {
+            StoredTime::Date(date) => DatePerhapsTime::Date(date),
+            StoredTime::Floating(date_time) => CalendarDateTime::Floating(date_time).into(),
+            StoredTime::Utc(date_time) => CalendarDateTime::Utc(date_time).into(),
+            StoredTime::WithTimezone { date_time, tzid } => {
+                CalendarDateTime::WithTimezone { date_time, tzid }.into()
+            }
+        })
+    }
+}
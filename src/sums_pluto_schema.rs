@@ -1,8 +1,5 @@
-
 use serde::{Deserialize, Serialize};
 
-pub type Pages = Vec<Page>;
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Page {
     pub(crate) current_page: i64,
@@ -0,0 +1,250 @@
+use axum::http::StatusCode;
+use chrono::{TimeDelta, Utc};
+
+use icalendar::Component;
+
+use super::{config, find, get_calendar, get_path, summaries, upstream};
+use crate::{
+    router,
+    source::{FetchOptions, Premium},
+};
+
+#[tokio::test]
+async fn past_events_are_included_with_history() {
+    let config = config(&upstream().await);
+    let archive = config.event_archive.clone().unwrap();
+    let app = router(config).unwrap();
+
+    let (_, events) = get_calendar(&app, "/kent_union_calendar.ics").await;
+    assert_eq!(
+        summaries(&events),
+        ["Freshers Fair", "Freshers Party", "Rowing Taster Session"]
+    );
+
+    // An event from earlier in the term, which upstream no longer lists.
+    let now = Utc::now();
+    let mut past = archive
+        .events(
+            "kent-union",
+            &FetchOptions::default(),
+            now - TimeDelta::days(3650),
+            now + TimeDelta::days(3650),
+        )
+        .await
+        .unwrap()
+        .remove(0);
+    past.uid = "200".to_owned();
+    past.title = "Welcome Week Quiz".to_owned();
+    past.start = (now - TimeDelta::days(30)).into();
+    past.end = (now - TimeDelta::days(30) + TimeDelta::hours(2)).into();
+    archive
+        .record("kent-union", &FetchOptions::default(), &[past])
+        .await
+        .unwrap();
+
+    let (_, events) = get_calendar(&app, "/kent_union_calendar.ics").await;
+    assert!(!summaries(&events).contains(&"Welcome Week Quiz"));
+
+    let (_, events) = get_calendar(&app, "/kent_union_calendar.ics?history=7d").await;
+    assert!(!summaries(&events).contains(&"Welcome Week Quiz"));
+
+    let (_, events) = get_calendar(&app, "/kent_union_calendar.ics?history=90d").await;
+    assert_eq!(
+        summaries(&events),
+        [
+            "Freshers Fair",
+            "Freshers Party",
+            "Rowing Taster Session",
+            "Welcome Week Quiz"
+        ]
+    );
+
+    let (status, _) = get_path(&app, "/kent_union_calendar.ics?history=forever").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Further back than dates go.
+    let (status, _) = get_path(&app, "/kent_union_calendar.ics?history=99999999d").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = get_path(&app, "/kent_union_calendar.ics?history=5300w").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = get_path(&app, "/kent_union_calendar.ics?history=5200w").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn archive_is_served_by_year() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (body, events) = get_calendar(&app, "/archive/2026/kent_union_calendar.ics").await;
+    assert!(body.contains("X-WR-CALNAME:Kent SU Calendar (2026)"));
+    assert_eq!(
+        summaries(&events),
+        ["Freshers Fair", "Freshers Party", "Rowing Taster Session"]
+    );
+
+    let (_, events) = get_calendar(&app, "/archive/2026/kent_public_calendar_medway.ics").await;
+    assert_eq!(
+        summaries(&events),
+        ["Medway Sports Night", "Research Webinar"]
+    );
+
+    let (_, events) = get_calendar(&app, "/archive/2025/kent_union_calendar.ics").await;
+    assert!(events.is_empty());
+}
+
+#[tokio::test]
+async fn changed_events_get_a_new_sequence() {
+    let config = config(&upstream().await);
+    let archive = config.event_archive.clone().unwrap();
+    let app = router(config).unwrap();
+
+    let (_, events) = get_calendar(&app, "/kent_public_calendar.ics").await;
+    let fair = find(&events, "Careers Fair");
+    assert_eq!(fair.get_sequence(), Some(0));
+    assert_eq!(
+        fair.property_value("LAST-MODIFIED"),
+        Some("20260915T110000Z")
+    );
+    assert_eq!(fair.property_value("DTSTAMP"), Some("20260915T110000Z"));
+    let night = find(&events, "Medway Sports Night");
+    assert_eq!(night.get_sequence(), Some(0));
+    let stamp = night.property_value("DTSTAMP").unwrap().to_owned();
+    assert_eq!(night.property_value("LAST-MODIFIED"), Some(stamp.as_str()));
+
+    let now = Utc::now();
+    let mut listed = archive
+        .events(
+            "kent-public",
+            &FetchOptions::default(),
+            now - TimeDelta::days(3650),
+            now + TimeDelta::days(3650),
+        )
+        .await
+        .unwrap();
+    let original = listed.clone();
+
+    // Refreshing an unchanged listing keeps the same revision.
+    archive.revise("kent-public", &mut listed).await.unwrap();
+    for (event, original) in listed.iter().zip(&original) {
+        assert_eq!(event.revision, original.revision);
+    }
+
+    // Moving an event is a new revision.
+    listed[0].location = Some("Templeman Library".to_owned());
+    archive.revise("kent-public", &mut listed).await.unwrap();
+    let (moved, before) = (listed[0].revision.unwrap(), original[0].revision.unwrap());
+    assert_eq!(moved.sequence, 1);
+    assert_eq!(moved.created, before.created);
+    assert!(moved.modified > before.modified);
+    assert_eq!(listed[1].revision, original[1].revision);
+}
+
+#[tokio::test]
+async fn events_that_vanish_are_cancelled() {
+    let config = config(&upstream().await);
+    let archive = config.event_archive.clone().unwrap();
+    let app = router(config).unwrap();
+
+    // Listed by upstream the last time it was fetched with these options,
+    // but not any more.
+    let (_, events) = get_calendar(&app, "/kent_public_calendar.ics").await;
+    assert_eq!(events.len(), 3);
+    let now = Utc::now();
+    let options = FetchOptions {
+        premium: Premium::All,
+    };
+    let mut open_day = archive
+        .events(
+            "kent-public",
+            &FetchOptions::default(),
+            now - TimeDelta::days(3650),
+            now + TimeDelta::days(3650),
+        )
+        .await
+        .unwrap()
+        .remove(0);
+    open_day.uid = "100".to_owned();
+    open_day.title = "Open Day".to_owned();
+    open_day.start = (now + TimeDelta::days(7)).into();
+    open_day.end = (now + TimeDelta::days(7) + TimeDelta::hours(6)).into();
+    archive
+        .record("kent-public", &options, &[open_day])
+        .await
+        .unwrap();
+
+    let (_, events) = get_calendar(&app, "/kent_public_calendar.ics?premium=all").await;
+    assert_eq!(
+        summaries(&events),
+        [
+            "Careers Fair",
+            "Medway Sports Night",
+            "Open Day",
+            "Research Webinar"
+        ]
+    );
+    let open_day = find(&events, "Open Day");
+    assert_eq!(open_day.property_value("STATUS"), Some("CANCELLED"));
+    assert!(open_day
+        .get_description()
+        .unwrap()
+        .ends_with("This event is no longer listed, so it may have been cancelled."));
+    assert_eq!(find(&events, "Careers Fair").property_value("STATUS"), None);
+}
+
+#[tokio::test]
+async fn sources_listing_the_same_event_keep_their_own_revisions() {
+    let config = config(&upstream().await);
+    let archive = config.event_archive.clone().unwrap();
+    let app = router(config).unwrap();
+
+    // Both list the Careers Fair, with a different link to it.
+    get_calendar(&app, "/kent_public_calendar.ics").await;
+    get_calendar(&app, "/kent_student_calendar.ics").await;
+    let now = Utc::now();
+    let mut listings = Vec::new();
+    for source in ["kent-public", "kent-student"] {
+        let events = archive
+            .events(
+                source,
+                &FetchOptions::default(),
+                now - TimeDelta::days(3650),
+                now + TimeDelta::days(3650),
+            )
+            .await
+            .unwrap();
+        assert!(events.iter().any(|event| event.title == "Careers Fair"));
+        listings.push((source, events));
+    }
+    let original = listings.clone();
+
+    // Refreshing each source in turn keeps every revision.
+    for _ in 0..2 {
+        for (source, events) in &mut listings {
+            archive.revise(source, events).await.unwrap();
+        }
+    }
+    for ((_, events), (_, original)) in listings.iter().zip(&original) {
+        for (event, original) in events.iter().zip(original) {
+            assert_eq!(event.revision, original.revision, "{}", event.title);
+            assert_eq!(event.revision.unwrap().sequence, 0);
+        }
+    }
+}
+
+#[tokio::test]
+async fn empty_listings_cancel_nothing() {
+    let config = config(&upstream().await);
+    let archive = config.event_archive.clone().unwrap();
+    let app = router(config).unwrap();
+
+    let (_, events) = get_calendar(&app, "/kent_public_calendar.ics").await;
+    assert_eq!(events.len(), 3);
+
+    // Such as when upstream renames the list of events.
+    let mut listed = Vec::new();
+    archive
+        .update("kent-public", &FetchOptions::default(), &mut listed)
+        .await
+        .unwrap();
+    assert!(listed.is_empty());
+}
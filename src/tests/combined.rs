@@ -0,0 +1,91 @@
+use chrono::{TimeDelta, Utc};
+use icalendar::Component;
+
+use super::{config, find, get_calendar, summaries, upstream};
+use crate::{
+    merge::merge,
+    router,
+    source::{Feed, FetchOptions},
+};
+
+#[tokio::test]
+async fn combined_calendar_collapses_duplicates() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (_, events) = get_calendar(
+        &app,
+        "/combined.ics?sources=kent-public,kent-student,kent-union",
+    )
+    .await;
+
+    assert_eq!(
+        summaries(&events),
+        [
+            "Careers Fair",
+            "Freshers Fair",
+            "Freshers Party",
+            "Medway Sports Night",
+            "Research Webinar",
+            "Rowing Taster Session"
+        ]
+    );
+    let fair = find(&events, "Careers Fair");
+    assert!(fair
+        .get_description()
+        .unwrap()
+        .ends_with("Listed in: Kent Public Calendar, Kent Student Calendar"));
+    let freshers = find(&events, "Freshers Fair");
+    assert!(freshers
+        .get_description()
+        .unwrap()
+        .ends_with("Listed in: Kent Student Calendar, Kent Union Calendar"));
+}
+
+#[tokio::test]
+async fn events_from_one_source_are_not_merged_by_title() {
+    let config = config(&upstream().await);
+    let archive = config.event_archive.clone().unwrap();
+    let app = router(config).unwrap();
+    get_calendar(&app, "/kent_union_calendar.ics").await;
+    let now = Utc::now();
+    let social = archive
+        .events(
+            "kent-union",
+            &FetchOptions::default(),
+            now - TimeDelta::days(3650),
+            now + TimeDelta::days(3650),
+        )
+        .await
+        .unwrap()
+        .remove(0);
+
+    // Two societies' socials at the same time, and another source's copy
+    // of one of them.
+    let socials = (1..=3)
+        .map(|id| {
+            let mut social = social.clone();
+            social.uid = format!("social-{id}");
+            social.origin = if id == 3 { "elsewhere" } else { "union" }.to_owned();
+            social.title = "Welcome Social".to_owned();
+            social
+        })
+        .collect::<Vec<_>>();
+    let feed = |events: &[_]| Feed {
+        title: String::new(),
+        description: String::new(),
+        events: events.to_vec(),
+    };
+    let (union, elsewhere) = (feed(&socials[..2]), feed(&socials[2..]));
+
+    let merged = merge([("Union", &union), ("Elsewhere", &elsewhere)]);
+
+    let uids = merged
+        .iter()
+        .map(|event| event.uid.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(uids, ["social-1", "social-2"]);
+    assert!(merged[0]
+        .description
+        .ends_with("Listed in: Union, Elsewhere"));
+    assert!(merged[1].description.ends_with("Listed in: Union"));
+}
@@ -0,0 +1,421 @@
+use axum::http::StatusCode;
+use icalendar::{Component, EventLike};
+
+use super::{
+    all_properties, categories, config, find, get_calendar, get_path, property, summaries, upstream,
+};
+use crate::{
+    calendars::KentSource,
+    router,
+    source::{CalendarSource, FetchOptions},
+};
+
+#[tokio::test]
+async fn kent_page_is_converted_to_calendar() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (body, events) = get_calendar(&app, "/kent_public_calendar.ics").await;
+
+    assert!(body.contains("X-WR-CALNAME:What's On - University of Kent"));
+    assert_eq!(
+        summaries(&events),
+        ["Careers Fair", "Medway Sports Night", "Research Webinar"]
+    );
+    let fair = find(&events, "Careers Fair");
+    assert_eq!(
+        fair.get_url(),
+        Some("https://www.kent.ac.uk/whats-on/101/careers-fair")
+    );
+    assert_eq!(fair.get_location(), Some("Sports Centre"));
+    assert_eq!(fair.get_uid(), Some("101@kent.ac.uk"));
+    assert_eq!(fair.property_value("DTSTART"), Some("20261021T100000"));
+
+    let (_, body) = get_path(&app, "/diagnostics/drift").await;
+    let reports: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let report = &reports["kent-public"];
+    assert_eq!(report["missing_fields"], serde_json::json!({}));
+    assert_eq!(report["unknown_fields"], serde_json::json!({}));
+    assert_eq!(report["unknown_values"], serde_json::json!({}));
+}
+
+#[tokio::test]
+async fn kent_page_built_by_scripts_is_evaluated() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (_, events) = get_calendar(&app, "/kent_student_calendar.ics").await;
+
+    assert_eq!(summaries(&events), ["Careers Fair", "Freshers Fair"]);
+    // This calendar keeps the UIDs it had before they were namespaced.
+    assert_eq!(find(&events, "Freshers Fair").get_uid(), Some("104"));
+}
+
+#[tokio::test]
+async fn kent_scripts_over_the_budget_are_skipped() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    // The first script never finishes, but the data is built by the next.
+    let (_, events) = get_calendar(&app, "/kent_scripts_calendar.ics").await;
+
+    assert_eq!(summaries(&events), ["Scripted Lecture"]);
+    assert_eq!(
+        find(&events, "Scripted Lecture").get_location(),
+        Some("Keynes College")
+    );
+}
+
+#[tokio::test]
+async fn kent_calendar_is_filtered_by_category_and_tag() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (_, events) = get_calendar(&app, "/kent_public_calendar.ics?category=careers").await;
+    assert_eq!(summaries(&events), ["Careers Fair"]);
+
+    let (_, events) = get_calendar(&app, "/kent_public_calendar.ics?tag=-free").await;
+    assert_eq!(summaries(&events), ["Research Webinar"]);
+
+    let (_, events) = get_calendar(
+        &app,
+        "/kent_public_calendar.ics?category=Sport&category=talks",
+    )
+    .await;
+    assert_eq!(
+        summaries(&events),
+        ["Medway Sports Night", "Research Webinar"]
+    );
+}
+
+#[tokio::test]
+async fn kent_calendar_is_filtered_by_campus() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (_, events) = get_calendar(&app, "/kent_public_calendar_medway.ics").await;
+    assert_eq!(
+        summaries(&events),
+        ["Medway Sports Night", "Research Webinar"]
+    );
+
+    let (_, events) = get_calendar(&app, "/kent_public_calendar.ics?campus=canterbury").await;
+    assert_eq!(summaries(&events), ["Careers Fair", "Research Webinar"]);
+
+    let (status, _) = get_path(&app, "/kent_public_calendar.ics?campus=dover").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn online_events_have_conference_and_no_location() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (_, events) = get_calendar(&app, "/kent_public_calendar.ics?mode=online").await;
+
+    assert_eq!(summaries(&events), ["Research Webinar"]);
+    let webinar = find(&events, "Research Webinar");
+    assert_eq!(webinar.get_location(), None);
+    let conference = property(webinar, "CONFERENCE").unwrap();
+    assert_eq!(
+        conference.value(),
+        "https://www.kent.ac.uk/whats-on/103/research-webinar"
+    );
+    assert_eq!(conference.params()["FEATURE"].value(), "VIDEO");
+
+    let (_, events) = get_calendar(&app, "/kent_public_calendar.ics?mode=in-person").await;
+    assert_eq!(summaries(&events), ["Careers Fair", "Medway Sports Night"]);
+}
+
+#[tokio::test]
+async fn schema_drift_is_tolerated_and_reported() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (_, events) = get_calendar(&app, "/kent_drift_calendar.ics").await;
+    assert_eq!(summaries(&events), ["Brussels Careers Fair"]);
+    let fair = find(&events, "Brussels Careers Fair");
+    assert_eq!(fair.property_value("DTSTART"), Some("20270120T100000"));
+
+    let (status, body) = get_path(&app, "/diagnostics/drift").await;
+    assert_eq!(status, StatusCode::OK);
+    let reports: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let report = &reports["kent-drift"];
+    assert_eq!(report["missing_fields"]["events[].series_title"], 1);
+    assert_eq!(report["missing_fields"]["events[].pricing"], 1);
+    assert_eq!(report["missing_fields"]["events[].subtitle"], 1);
+    assert_eq!(report["unknown_fields"]["events[].livestream_url"], 1);
+    assert_eq!(
+        report["unknown_values"]["events[].campus_name"],
+        serde_json::json!(["Brussels"])
+    );
+    assert_eq!(
+        report["unknown_values"]["events[].banner_image.focus"],
+        serde_json::json!(["top"])
+    );
+    assert!(report["unknown_values"]
+        .get("events[].start_month")
+        .is_none());
+}
+
+#[tokio::test]
+async fn whole_day_events_are_dates() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (body, events) = get_calendar(&app, "/kent_exhibitions_calendar.ics").await;
+
+    assert!(body.contains("DTSTART;VALUE=DATE:20261107"));
+    let open_day = find(&events, "Open Day");
+    assert_eq!(open_day.property_value("DTSTART"), Some("20261107"));
+    assert_eq!(open_day.property_value("DTEND"), Some("20261108"));
+
+    let conference = find(&events, "Research Conference");
+    assert_eq!(conference.property_value("DTSTART"), Some("20261110"));
+    assert_eq!(conference.property_value("DTEND"), Some("20261112"));
+
+    // Longer than `long_event_days`, so only shown on its first day.
+    let exhibition = find(&events, "Sculpture Exhibition");
+    assert_eq!(exhibition.property_value("DTSTART"), Some("20261102"));
+    assert_eq!(exhibition.property_value("DTEND"), Some("20261103"));
+    assert!(exhibition
+        .get_description()
+        .unwrap()
+        .ends_with("\n\nRuns until Thursday 17 December 2026."));
+
+    // Runs past midnight, but isn't a whole day.
+    let concert = find(&events, "Evening Concert");
+    assert_eq!(concert.property_value("DTSTART"), Some("20261112T193000"));
+    assert_eq!(concert.property_value("DTEND"), Some("20261113T003000"));
+}
+
+#[tokio::test]
+async fn kent_images_are_resolved_and_described() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (body, events) = get_calendar(&app, "/kent_public_calendar.ics").await;
+
+    let images = all_properties(&body, "IMAGE");
+    let image = |url: &str| {
+        &images
+            .iter()
+            .find(|(value, _)| value == url)
+            .unwrap_or_else(|| panic!("no image {url}"))
+            .1
+    };
+    let banner = image("https://media.kent.ac.uk/events/careers-fair.jpg");
+    assert_eq!(banner["DISPLAY"], "FULLSIZE");
+    assert_eq!(banner["FMTTYPE"], "image/jpeg");
+    assert_eq!(banner["X-ALT-TEXT"], "Careers Fair banner");
+    let thumbnail = image("https://media.kent.ac.uk/events/careers-fair-thumb.jpg");
+    assert_eq!(thumbnail["DISPLAY"], "THUMBNAIL");
+    // Relative to the page's assets.
+    let card = image("https://www.kent.ac.uk/assets/images/careers-fair-card.png");
+    assert_eq!(card["DISPLAY"], "GRAPHIC");
+    assert_eq!(card["FMTTYPE"], "image/png");
+    assert_eq!(card["X-ALT-TEXT"], "Students at the Careers Fair");
+    // Only attached when the calendar asks for it.
+    assert!(property(find(&events, "Careers Fair"), "ATTACH").is_none());
+}
+
+#[tokio::test]
+async fn kent_categories_and_tags_are_renamed() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (body, _) = get_calendar(&app, "/kent_public_calendar.ics").await;
+
+    // The "free" tag is left out.
+    assert_eq!(
+        categories(&body, "Careers Fair"),
+        ["Careers & Employability", "Networking"]
+    );
+    assert_eq!(categories(&body, "Medway Sports Night"), ["Sport"]);
+}
+
+#[tokio::test]
+async fn kent_locations_are_found_in_the_gazetteer() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (_, events) = get_calendar(&app, "/kent_student_calendar.ics").await;
+
+    // Only the part before the comma is a place, so the rest is kept.
+    let fair = find(&events, "Freshers Fair");
+    assert_eq!(
+        fair.get_location(),
+        Some("Rutherford LT1, Canterbury campus")
+    );
+    assert_eq!(fair.property_value("GEO"), Some("51.2986;1.07"));
+    let pin = property(fair, "X-APPLE-STRUCTURED-LOCATION").unwrap();
+    assert_eq!(pin.value(), "geo:51.2986,1.07");
+    assert_eq!(
+        pin.params()["X-TITLE"].value(),
+        "Rutherford LT1, Canterbury campus"
+    );
+    assert_eq!(
+        pin.params()["X-ADDRESS"].value(),
+        "Rutherford College, University of Kent, Canterbury CT2 7NX"
+    );
+
+    let (_, events) = get_calendar(&app, "/kent_public_calendar.ics").await;
+
+    // Online events have no place.
+    let webinar = find(&events, "Research Webinar");
+    assert!(property(webinar, "GEO").is_none());
+    assert!(property(webinar, "X-APPLE-STRUCTURED-LOCATION").is_none());
+
+    let (_, events) = get_calendar(&app, "/kent_exhibitions_calendar.ics").await;
+
+    let open_day = find(&events, "Open Day");
+    assert_eq!(open_day.get_location(), Some("Sports Centre"));
+    assert_eq!(open_day.property_value("GEO"), Some("51.2968;1.0594"));
+    let conference = find(&events, "Research Conference");
+    assert_eq!(
+        conference.get_location(),
+        Some("Rutherford College, Seminar Room 5")
+    );
+    assert_eq!(conference.property_value("GEO"), Some("51.2986;1.07"));
+
+    // Canterbury's Sports Centre isn't at Medway.
+    let concert = find(&events, "Evening Concert");
+    assert_eq!(concert.get_location(), Some("Sports Centre"));
+    assert!(property(concert, "GEO").is_none());
+}
+
+#[tokio::test]
+async fn kent_contacts_are_organizers() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (_, events) = get_calendar(&app, "/kent_public_calendar.ics").await;
+
+    let fair = find(&events, "Careers Fair");
+    let organizer = property(fair, "ORGANIZER").unwrap();
+    assert_eq!(organizer.value(), "mailto:events@kent.ac.uk");
+    assert_eq!(organizer.params()["CN"].value(), "Events Team");
+    assert_eq!(
+        property(fair, "CONTACT").unwrap().value(),
+        "Events Team, events@kent.ac.uk, 01227 764000"
+    );
+
+    // Without an email address, there's no one to be the organizer.
+    let webinar = find(&events, "Research Webinar");
+    assert!(property(webinar, "ORGANIZER").is_none());
+    assert_eq!(
+        property(webinar, "CONTACT").unwrap().value(),
+        "Research Office"
+    );
+}
+
+#[tokio::test]
+async fn kent_descriptions_are_plain_text_with_html_alternative() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (_, events) = get_calendar(&app, "/kent_public_calendar.ics").await;
+
+    let fair = find(&events, "Careers Fair");
+    assert_eq!(
+        fair.get_description(),
+        Some(
+            "Meet employers from over 50 companies & charities.\n\n\
+            • Bring your CV\n\
+            • Dress smart\n\n\
+            Book at the careers site [1] or email careers@kent.ac.uk.\n\n\
+            See you there!\n\n\
+            Links:\n\
+            [1] https://www.kent.ac.uk/careers/fair"
+        )
+    );
+    let html = property(fair, "X-ALT-DESC").unwrap();
+    assert_eq!(html.params()["FMTTYPE"].value(), "text/html");
+    // `icalendar` doesn't unescape properties it doesn't know are text.
+    let unescaped = html.value().replace("\\n", "\n").replace("\\;", ";");
+    assert_eq!(
+        unescaped,
+        "<p>Meet employers from <strong>over 50</strong> companies &amp; charities.</p>\n\
+        <ul>\n  <li>Bring your CV</li>\n  <li><p>Dress smart</p></li>\n</ul>\n\
+        <p>Book at <a href=\"https://www.kent.ac.uk/careers/fair\">the careers site</a> \
+        or email <a href=\"mailto:careers@kent.ac.uk\">careers@kent.ac.uk</a>.</p>\
+        <p>See you there!</p>"
+    );
+}
+
+#[tokio::test]
+async fn kent_tickets_are_described_and_filtered() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (_, events) = get_calendar(&app, "/kent_public_calendar.ics?bookable=true").await;
+
+    assert_eq!(summaries(&events), ["Medway Sports Night"]);
+    assert!(find(&events, "Medway Sports Night")
+        .get_description()
+        .unwrap()
+        .ends_with(
+            "Tickets (limited availability)\n\
+            Price: £5, book online\n\
+            Open to: Students\n\
+            Limited places\n\
+            Book at: https://www.kent.ac.uk/whats-on/102/medway-sports-night"
+        ));
+
+    let (_, events) = get_calendar(&app, "/kent_public_calendar.ics?bookable=false").await;
+    assert_eq!(summaries(&events), ["Careers Fair", "Research Webinar"]);
+
+    let (status, _) = get_path(&app, "/kent_public_calendar.ics?bookable=maybe").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Free and open to everyone, so there's nothing to say.
+    assert!(!find(&events, "Careers Fair")
+        .get_description()
+        .unwrap()
+        .contains("Tickets"));
+
+    // Saying that tickets or booking aren't needed doesn't make an event
+    // bookable, or sold out.
+    let (_, events) = get_calendar(&app, "/kent_exhibitions_calendar.ics?bookable=true").await;
+    assert!(events.is_empty());
+    let (_, events) = get_calendar(&app, "/kent_exhibitions_calendar.ics").await;
+    assert!(find(&events, "Research Conference")
+        .get_description()
+        .unwrap()
+        .ends_with("Tickets\nPrice: Free\nOpen to: Everyone\nNo tickets required"));
+    assert!(find(&events, "Evening Concert")
+        .get_description()
+        .unwrap()
+        .ends_with(
+            "Tickets\nPrice: Free, no booking required\nOpen to: Everyone\nNo ticket needed"
+        ));
+    assert!(find(&events, "Open Day")
+        .get_description()
+        .unwrap()
+        .ends_with(
+            "Tickets\n\
+            Price: Free, no need to register\n\
+            Open to: Everyone\n\
+            No registration required"
+        ));
+}
+
+#[tokio::test]
+async fn unexpected_kent_pages_are_errors() {
+    let base = upstream().await;
+    let source = |path: &str| KentSource {
+        id: "kent-unexpected".to_owned(),
+        name: "Kent Unexpected Calendar".to_owned(),
+        url: format!("{base}{path}"),
+        title: None,
+        description: None,
+        event_url: "{events_base_url}/{id}/{slug}".to_owned(),
+        long_event_days: None,
+        script_runner: Default::default(),
+        gazetteer: Default::default(),
+        drift: Default::default(),
+    };
+
+    let error = source("/blocked")
+        .fetch(&FetchOptions::default())
+        .await
+        .unwrap_err();
+    assert_eq!(error.to_string(), format!("failed to fetch {base}/blocked"));
+    assert!(error.root_cause().to_string().contains("403 Forbidden"));
+
+    let error = source("/redesign")
+        .fetch(&FetchOptions::default())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        format!("unexpected page at {base}/redesign")
+    );
+    assert_eq!(error.root_cause().to_string(), "page has no title");
+}
@@ -0,0 +1,104 @@
+use std::time::{Duration, Instant};
+
+use super::{KENT_STUDENT_EVENTS, KENT_WHATS_ON};
+use crate::{
+    config::ScriptConfig,
+    kent_data::{assigned_literal, extract, ScriptRunner},
+};
+
+/// The scripts on a page.
+fn scripts(page: &str) -> Vec<String> {
+    let selector = scraper::Selector::parse("script").unwrap();
+    scraper::Html::parse_document(page)
+        .select(&selector)
+        .map(|script| script.text().collect())
+        .collect()
+}
+
+/// A runner that can never evaluate anything, so only the literal can be read.
+fn no_evaluation() -> ScriptRunner {
+    ScriptRunner::new(ScriptConfig {
+        timeout_ms: 100,
+        max_concurrent: 0,
+        ..ScriptConfig::default()
+    })
+}
+
+#[test]
+fn literals_assigned_to_kent_are_found() {
+    assert_eq!(
+        assigned_literal("window.KENT = {\"events\": []};"),
+        Some("{\"events\": []}")
+    );
+    assert_eq!(
+        assigned_literal("var KENT={a: [1, {b: 2}]}"),
+        Some("{a: [1, {b: 2}]}")
+    );
+    // Braces in strings don't end the object.
+    assert_eq!(
+        assigned_literal(r#"KENT = {"a": "}", 'b': '{', c: `}\`}`}; x = {}"#),
+        Some(r#"{"a": "}", 'b': '{', c: `}\`}`}"#)
+    );
+    // Other names, comparisons and other objects' properties aren't it.
+    assert_eq!(assigned_literal("KENT_CONFIG = {a: 1}"), None);
+    assert_eq!(assigned_literal("MY_KENT = {a: 1}"), None);
+    assert_eq!(assigned_literal("if (KENT == {}) {}"), None);
+    assert_eq!(assigned_literal("other.KENT = {a: 1}"), None);
+    assert_eq!(
+        assigned_literal("KENT_CONFIG = {}; window.KENT = {a: 1}"),
+        Some("{a: 1}")
+    );
+    // Objects built in code have to be evaluated.
+    assert_eq!(assigned_literal("window.KENT = Object.assign({}, x)"), None);
+    assert_eq!(assigned_literal("window.KENT = {a: [1, 2}"), None);
+}
+
+#[tokio::test]
+async fn kent_literals_are_read_without_evaluation() {
+    let (data, _) = extract(scripts(KENT_WHATS_ON), &no_evaluation())
+        .await
+        .unwrap();
+    assert_eq!(data.events.len(), 3);
+
+    // This page's literal refers to a variable, so it has to be evaluated.
+    let error = extract(scripts(KENT_STUDENT_EVENTS), &no_evaluation())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "script evaluation exceeded its time budget"
+    );
+    extract(scripts(KENT_STUDENT_EVENTS), &ScriptRunner::default())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn evaluations_over_the_time_budget_fail_quickly() {
+    // Runs for much longer than the budget, but does finish.
+    let scripts = vec!["for (var i = 0; i < 1e9; i++) {}".to_owned()];
+    let runner = ScriptRunner::new(ScriptConfig {
+        timeout_ms: 100,
+        loop_iteration_limit: 2_000_000,
+        max_concurrent: 1,
+        ..ScriptConfig::default()
+    });
+
+    let started = Instant::now();
+    let error = extract(scripts.clone(), &runner).await.unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "script evaluation exceeded its time budget"
+    );
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    // The abandoned evaluation is still running, so there's no turn free, but
+    // waiting for one is within the budget too.
+    let started = Instant::now();
+    let error = extract(scripts, &runner).await.unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "script evaluation exceeded its time budget"
+    );
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
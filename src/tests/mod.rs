@@ -0,0 +1,276 @@
+//! End-to-end tests, run against recorded copies of the upstream sites.
+
+use std::collections::BTreeMap;
+
+use axum::{
+    body::Body,
+    extract::Query,
+    http::{HeaderMap, Request, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use icalendar::{Calendar, CalendarComponent, Component};
+use tokio::net::TcpListener;
+use tower::ServiceExt;
+
+use crate::config::Config;
+
+mod archive;
+mod combined;
+mod kent;
+mod kent_data;
+mod sums;
+
+const KENT_WHATS_ON: &str = include_str!("../../tests/fixtures/kent_whats_on.html");
+const KENT_STUDENT_EVENTS: &str = include_str!("../../tests/fixtures/kent_student_events.html");
+/// A Kent page with all day and multi-day events.
+const KENT_EXHIBITIONS: &str = include_str!("../../tests/fixtures/kent_exhibitions.html");
+/// A Kent page whose data has fields and values missing from the schema.
+const KENT_DRIFT: &str = include_str!("../../tests/fixtures/kent_drift.html");
+/// A Kent page whose data is built by scripts, after one that never finishes.
+const KENT_SCRIPTS: &str = include_str!("../../tests/fixtures/kent_scripts.html");
+const PLUTO_PAGE_1: &str = include_str!("../../tests/fixtures/pluto_events_page_1.json");
+const PLUTO_PAGE_2: &str = include_str!("../../tests/fixtures/pluto_events_page_2.json");
+
+/// The SUMS site id served by the mock upstream.
+const SITE_ID: &str = "testsite";
+
+/// Start a mock of the Kent websites and the SUMS Pluto API, returning its
+/// base URL.
+async fn upstream() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+
+    let app = Router::new()
+        .route("/whats-on", get(|| async { Html(KENT_WHATS_ON) }))
+        .route("/events", get(|| async { Html(KENT_STUDENT_EVENTS) }))
+        .route("/drift", get(|| async { Html(KENT_DRIFT) }))
+        .route("/exhibitions", get(|| async { Html(KENT_EXHIBITIONS) }))
+        .route("/scripts", get(|| async { Html(KENT_SCRIPTS) }))
+        .route(
+            "/blocked",
+            get(|| async {
+                (
+                    StatusCode::FORBIDDEN,
+                    Html("<html><head><title>Access denied</title></head></html>"),
+                )
+            }),
+        )
+        .route(
+            "/redesign",
+            get(|| async { Html("<html><body><h1>What's On</h1></body></html>") }),
+        )
+        .route(
+            "/api/events",
+            get({
+                let base = base.clone();
+                move |headers, query| pluto_events(base, headers, query)
+            }),
+        );
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    base
+}
+
+/// Serves the recorded pages of the Pluto events API.
+///
+/// Like the real API, events that aren't premium are only listed without
+/// `onlyPremium=1`.
+async fn pluto_events(
+    base: String,
+    headers: HeaderMap,
+    Query(params): Query<Vec<(String, String)>>,
+) -> Response {
+    if headers.get("X-Site-Id").and_then(|id| id.to_str().ok()) != Some(SITE_ID) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let page = match param("page") {
+        None | Some("1") => PLUTO_PAGE_1,
+        Some("2") => PLUTO_PAGE_2,
+        Some(_) => return StatusCode::NOT_FOUND.into_response(),
+    };
+
+    let mut page: serde_json::Value = serde_json::from_str(&page.replace("{base}", &base)).unwrap();
+    if param("onlyPremium") == Some("1") {
+        page["data"]
+            .as_array_mut()
+            .unwrap()
+            .retain(|event| !event["premium"].is_null());
+    }
+    Json(page).into_response()
+}
+
+/// Configuration for the service, pointed at the mock upstream.
+fn config(base: &str) -> Config {
+    let config = r#"
+        [sums]
+        api_url = "{base}/api/events"
+
+        [archive]
+        path = ":memory:"
+
+        [scripts]
+        loop_iteration_limit = 100000
+
+        [categories]
+        Careers = "Careers & Employability"
+        "Club Night" = "Social"
+        free = ""
+
+        [[calendars]]
+        id = "kent-public"
+        path = "/kent_public_calendar.ics"
+        name = "Kent Public Calendar"
+        type = "kent"
+        url = "{base}/whats-on"
+
+        [[calendars.presets]]
+        path = "/kent_public_calendar_medway.ics"
+        name = "Kent Public Calendar (Medway)"
+        query = "campus=medway"
+
+        [[calendars]]
+        id = "kent-student"
+        path = "/kent_student_calendar.ics"
+        name = "Kent Student Calendar"
+        type = "kent"
+        url = "{base}/events"
+        uid_format = "legacy"
+
+        [[calendars]]
+        id = "kent-drift"
+        path = "/kent_drift_calendar.ics"
+        name = "Kent Drift Calendar"
+        type = "kent"
+        url = "{base}/drift"
+
+        [[calendars]]
+        id = "kent-exhibitions"
+        path = "/kent_exhibitions_calendar.ics"
+        name = "Kent Exhibitions Calendar"
+        type = "kent"
+        url = "{base}/exhibitions"
+        long_event_days = 14
+
+        [[calendars]]
+        id = "kent-scripts"
+        path = "/kent_scripts_calendar.ics"
+        name = "Kent Scripts Calendar"
+        type = "kent"
+        url = "{base}/scripts"
+
+        [[calendars]]
+        id = "kent-union"
+        path = "/kent_union_calendar.ics"
+        name = "Kent Union Calendar"
+        type = "sums"
+        site_id = "testsite"
+        title = "Kent SU Calendar"
+        description = "Hello Kent"
+        event_url = "https://hellokent.co.uk/events/id/{id}"
+        group_url = "https://hellokent.co.uk/groups/id/{id}"
+        attach_images = true
+    "#;
+    Config::parse(&config.replace("{base}", base)).unwrap()
+}
+
+/// Make a GET request to the service.
+async fn get_path(app: &Router, path: &str) -> (StatusCode, String) {
+    let response = app
+        .clone()
+        .oneshot(Request::get(path).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+/// Fetch and parse a calendar from the service, panicking if it fails.
+async fn get_calendar(app: &Router, path: &str) -> (String, Vec<icalendar::Event>) {
+    let (status, body) = get_path(app, path).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let calendar: Calendar = body.parse().unwrap();
+    let events = calendar
+        .components
+        .into_iter()
+        .filter_map(|component| match component {
+            CalendarComponent::Event(event) => Some(event),
+            _ => None,
+        })
+        .collect();
+    (body, events)
+}
+
+fn summaries(events: &[icalendar::Event]) -> Vec<&str> {
+    let mut summaries = events
+        .iter()
+        .filter_map(|event| event.get_summary())
+        .collect::<Vec<_>>();
+    summaries.sort_unstable();
+    summaries
+}
+
+fn find<'a>(events: &'a [icalendar::Event], summary: &str) -> &'a icalendar::Event {
+    events
+        .iter()
+        .find(|event| event.get_summary() == Some(summary))
+        .unwrap_or_else(|| panic!("no event called {summary}"))
+}
+
+/// The first value of a property, whether it was parsed as a single or multi
+/// property.
+fn property<'a>(event: &'a icalendar::Event, key: &str) -> Option<&'a icalendar::Property> {
+    event
+        .properties()
+        .get(key)
+        .or_else(|| event.multi_properties().get(key)?.first())
+}
+
+/// Every instance of a property in a calendar body, as its value and
+/// parameters, for properties `icalendar` only keeps one of when parsing.
+fn all_properties(body: &str, key: &str) -> Vec<(String, BTreeMap<String, String>)> {
+    let prefix = format!("{key};");
+    body.replace("\r\n ", "")
+        .lines()
+        .filter_map(|line| line.strip_prefix(&prefix))
+        .map(|line| {
+            let mut params = BTreeMap::new();
+            let mut rest = line;
+            loop {
+                let (name, after) = rest.split_once('=').unwrap();
+                let (value, after) = match after.strip_prefix('"') {
+                    Some(quoted) => quoted.split_once('"').unwrap(),
+                    None => after.split_at(after.find([';', ':']).unwrap()),
+                };
+                params.insert(name.to_owned(), value.to_owned());
+                match after.split_at(1) {
+                    (";", after) => rest = after,
+                    (_, value) => return (value.to_owned(), params),
+                }
+            }
+        })
+        .collect()
+}
+
+/// The categories of the event called `summary` in a calendar body, in order.
+fn categories(body: &str, summary: &str) -> Vec<String> {
+    let event = body
+        .split("BEGIN:VEVENT")
+        .find(|event| event.contains(&format!("\r\nSUMMARY:{summary}\r\n")))
+        .unwrap_or_else(|| panic!("no event called {summary}"));
+    event
+        .lines()
+        .filter_map(|line| line.strip_prefix("CATEGORIES:"))
+        .map(|category| category.replace("\\,", ","))
+        .collect()
+}
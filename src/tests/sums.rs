@@ -0,0 +1,238 @@
+use axum::http::StatusCode;
+use icalendar::{Component, EventLike};
+
+use super::{
+    all_properties, categories, config, find, get_calendar, get_path, property, summaries, upstream,
+};
+use crate::router;
+
+#[tokio::test]
+async fn sums_pages_are_followed() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (body, events) = get_calendar(&app, "/kent_union_calendar.ics").await;
+
+    assert!(body.contains("X-WR-CALNAME:Kent SU Calendar"));
+    assert_eq!(
+        summaries(&events),
+        ["Freshers Fair", "Freshers Party", "Rowing Taster Session"]
+    );
+    let party = find(&events, "Freshers Party");
+    assert_eq!(
+        party.get_url(),
+        Some("https://hellokent.co.uk/events/id/201")
+    );
+    assert_eq!(
+        party.get_location(),
+        Some("The Venue, University of Kent, Canterbury, CT2 7NZ, United Kingdom")
+    );
+    assert_eq!(party.get_uid(), Some("201@pluto.sums.su/testsite"));
+    assert_eq!(party.property_value("DTSTART"), Some("20261021T210000"));
+}
+
+#[tokio::test]
+async fn times_are_in_london_across_the_clock_change() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (body, events) = get_calendar(&app, "/kent_union_calendar.ics").await;
+
+    assert_eq!(body.matches("BEGIN:VTIMEZONE").count(), 1);
+    assert!(body.find("BEGIN:VTIMEZONE") < body.find("BEGIN:VEVENT"));
+    assert!(body.contains("RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU"));
+    assert!(!body.contains("DTSTART:2026"));
+
+    // Before the clocks go back, in British Summer Time.
+    let party = find(&events, "Freshers Party");
+    let start = property(party, "DTSTART").unwrap();
+    assert_eq!(start.value(), "20261021T210000");
+    assert_eq!(start.params()["TZID"].value(), "Europe/London");
+    assert_eq!(party.property_value("DTEND"), Some("20261022T020000"));
+
+    // The morning the clocks go back, in Greenwich Mean Time.
+    let rowing = find(&events, "Rowing Taster Session");
+    assert_eq!(rowing.property_value("DTSTART"), Some("20261025T090000"));
+}
+
+#[tokio::test]
+async fn sums_calendar_is_filtered() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (_, events) = get_calendar(&app, "/kent_union_calendar.ics?premium=all").await;
+    assert_eq!(
+        summaries(&events),
+        [
+            "Chess Club Social",
+            "Freshers Fair",
+            "Freshers Party",
+            "Quiz Night - POSTPONED",
+            "Rowing Taster Session"
+        ]
+    );
+    let quiz = find(&events, "Quiz Night - POSTPONED");
+    assert_eq!(quiz.property_value("STATUS"), Some("CANCELLED"));
+    let party = find(&events, "Freshers Party");
+    assert_eq!(party.property_value("STATUS"), None);
+
+    let (_, events) = get_calendar(
+        &app,
+        "/kent_union_calendar.ics?premium=all&group=chess-society",
+    )
+    .await;
+    assert_eq!(summaries(&events), ["Chess Club Social"]);
+
+    let (_, events) = get_calendar(&app, "/kent_union_calendar.ics?category=sport").await;
+    assert_eq!(summaries(&events), ["Rowing Taster Session"]);
+
+    let (_, events) = get_calendar(&app, "/kent_union_calendar.ics?type=-meeting&venue=7").await;
+    assert_eq!(summaries(&events), ["Freshers Party"]);
+}
+
+#[tokio::test]
+async fn sums_sites_are_served_by_id_and_alias() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (body, events) = get_calendar(&app, "/sums/testsite.ics").await;
+    assert!(body.contains("X-WR-CALNAME:Students' Union Calendar"));
+    assert_eq!(events.len(), 3);
+
+    let (body, _) = get_calendar(&app, "/sums/kent-union.ics").await;
+    assert!(body.contains("X-WR-CALNAME:Kent SU Calendar"));
+
+    let (status, _) = get_path(&app, "/sums/not-a-site!.ics").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn sums_images_are_attached_when_configured() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (body, events) = get_calendar(&app, "/kent_union_calendar.ics").await;
+
+    // The thumbnail and app thumbnail are the same image.
+    let images = all_properties(&body, "IMAGE");
+    assert_eq!(images.len(), 2);
+    let (url, params) = &images[0];
+    assert_eq!(url, "https://cdn.example.com/events/freshers-party.jpg");
+    assert_eq!(params["DISPLAY"], "FULLSIZE");
+    assert_eq!(params["X-ALT-TEXT"], "Freshers Party");
+    let (url, params) = &images[1];
+    assert!(url.ends_with("/assets/events/freshers-party-thumb.jpg"));
+    assert_eq!(params["DISPLAY"], "THUMBNAIL");
+
+    let party = find(&events, "Freshers Party");
+
+    let attachment = property(party, "ATTACH").unwrap();
+    assert_eq!(
+        attachment.value(),
+        "https://cdn.example.com/events/freshers-party.jpg"
+    );
+    assert_eq!(attachment.params()["FMTTYPE"].value(), "image/jpeg");
+    assert!(property(find(&events, "Freshers Fair"), "ATTACH").is_none());
+}
+
+#[tokio::test]
+async fn sums_categories_include_type_and_group() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (body, _) = get_calendar(&app, "/kent_union_calendar.ics").await;
+
+    // The "Club Night" type is renamed to the category it already has.
+    assert_eq!(categories(&body, "Freshers Party"), ["Social"]);
+    assert_eq!(
+        categories(&body, "Rowing Taster Session"),
+        ["Sport", "Meeting", "Rowing Club"]
+    );
+}
+
+#[tokio::test]
+async fn sums_venues_have_full_addresses() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (_, events) = get_calendar(&app, "/kent_union_calendar.ics?premium=all").await;
+
+    // On campus, positioned from the gazetteer, but with the union's address.
+    let party = find(&events, "Freshers Party");
+    assert_eq!(party.property_value("GEO"), Some("51.297;1.0683"));
+    assert_eq!(
+        property(party, "LOCATION").unwrap().params()["ALTREP"].value(),
+        "https://www.openstreetmap.org/?mlat=51.297&mlon=1.0683#map=18/51.297/1.0683"
+    );
+    let pin = property(party, "X-APPLE-STRUCTURED-LOCATION").unwrap();
+    assert_eq!(pin.value(), "geo:51.297,1.0683");
+    assert_eq!(pin.params()["X-TITLE"].value(), "The Venue");
+    assert_eq!(
+        pin.params()["X-ADDRESS"].value(),
+        "University of Kent, Canterbury, CT2 7NZ, United Kingdom"
+    );
+
+    // Off campus, so only the address is known.
+    let rowing = find(&events, "Rowing Taster Session");
+    assert_eq!(
+        rowing.get_location(),
+        Some("Canterbury Rowing Club, Kingsmead Road, Canterbury, CT2 7PH, United Kingdom")
+    );
+    assert!(property(rowing, "GEO").is_none());
+    // But it can still be found on a map.
+    assert_eq!(
+        property(rowing, "LOCATION").unwrap().params()["ALTREP"].value(),
+        "https://www.openstreetmap.org/search?query=Canterbury+Rowing+Club%2C+Kingsmead+Road%2C\
+        +Canterbury%2C+CT2+7PH%2C+United+Kingdom"
+    );
+
+    // Without an address, the venue is found in the gazetteer by name.
+    let chess = find(&events, "Chess Club Social");
+    assert_eq!(chess.get_location(), Some("Templeman Library"));
+    assert_eq!(chess.property_value("GEO"), Some("51.2979;1.0692"));
+}
+
+#[tokio::test]
+async fn sums_groups_are_organizers() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (_, events) = get_calendar(&app, "/kent_union_calendar.ics?premium=all").await;
+
+    let chess = find(&events, "Chess Club Social");
+    let organizer = property(chess, "ORGANIZER").unwrap();
+    assert_eq!(organizer.value(), "https://hellokent.co.uk/groups/id/31");
+    assert_eq!(organizer.params()["CN"].value(), "Chess Society");
+    let contact = property(chess, "CONTACT").unwrap();
+    assert_eq!(
+        contact.value(),
+        "Chess Society (Societies), https://hellokent.co.uk/groups/id/31"
+    );
+    assert_eq!(
+        contact.params()["ALTREP"].value(),
+        "https://hellokent.co.uk/groups/id/31"
+    );
+
+    // Events without a group have no organizer.
+    let party = find(&events, "Freshers Party");
+    assert!(property(party, "ORGANIZER").is_none());
+    assert!(property(party, "CONTACT").is_none());
+}
+
+#[tokio::test]
+async fn sums_tickets_are_described_and_filtered() {
+    let app = router(config(&upstream().await)).unwrap();
+
+    let (_, events) =
+        get_calendar(&app, "/kent_union_calendar.ics?premium=all&bookable=true").await;
+
+    assert_eq!(summaries(&events), ["Chess Club Social", "Freshers Party"]);
+    // Sold elsewhere.
+    assert!(find(&events, "Freshers Party")
+        .get_description()
+        .unwrap()
+        .ends_with("Tickets\nBook at: https://tickets.example.com/freshers-party"));
+    // Sold by the union, with every product hidden.
+    assert!(find(&events, "Chess Club Social")
+        .get_description()
+        .unwrap()
+        .ends_with("Tickets (sold out)\nBook at: https://hellokent.co.uk/events/id/202"));
+
+    let (_, events) = get_calendar(&app, "/kent_union_calendar.ics?bookable=false").await;
+    assert_eq!(
+        summaries(&events),
+        ["Freshers Fair", "Rowing Taster Session"]
+    );
+}